@@ -94,9 +94,11 @@ fn validation_example() -> Result<(), Box<dyn std::error::Error>> {
     println!("--- Validation Example ---");
 
     // Create invalid config
-    let mut invalid_postgres = PostgresConfig::default();
-    invalid_postgres.host = "".to_string(); // Invalid: empty host
-    invalid_postgres.password = "".to_string(); // Invalid: empty password
+    let invalid_postgres = PostgresConfig {
+        host: "".to_string(),     // Invalid: empty host
+        password: "".to_string(), // Invalid: empty password
+        ..PostgresConfig::default()
+    };
 
     let config = ConfigManager::builder()
         .with_postgres(invalid_postgres)
@@ -135,8 +137,10 @@ fn url_vs_components_example() -> Result<(), Box<dyn std::error::Error>> {
     // Demo 2: Using individual components (strict validation)
     std::env::remove_var("DATABASE_URL");
 
-    let mut config_components = PostgresConfig::default();
-    config_components.password = "".to_string(); // This will fail without DATABASE_URL
+    let config_components = PostgresConfig {
+        password: "".to_string(), // This will fail without DATABASE_URL
+        ..PostgresConfig::default()
+    };
 
     match config_components.validate() {
         Ok(_) => println!("❌ Unexpected: validation should have failed"),
@@ -0,0 +1,288 @@
+//! Command-line overrides
+//!
+//! Two repeatable flags:
+//!
+//! - `--config FILE` adds a YAML file (same rules as `with_yaml_files`, but a
+//!   missing file is an error since it was asked for explicitly).
+//! - `--set key.path=value` overrides a single plugin field. Overrides are the
+//!   highest-priority layer: they win over YAML files, directories and env
+//!   vars, whatever order the builder calls come in.
+//!
+//! ```text
+//! my-service --config /etc/tyl/service.yaml --set redis.port=6380 --set postgres.pool_size=20
+//! ```
+//!
+//! Values are checked against the field they replace: `--set redis.port=abc`
+//! fails with an error naming `redis.port`, and so does a key the plugin does
+//! not have. With the `clap` feature, [`ConfigArgs`] derives `clap::Args` and
+//! can be `#[command(flatten)]`-ed into an application's own parser.
+
+use crate::{registry, yaml, ConfigResult, FieldMetadata};
+use serde_yaml::Value;
+use std::path::PathBuf;
+use tyl_errors::TylError;
+
+/// Flag adding a YAML file
+pub const CONFIG_FLAG: &str = "--config";
+
+/// Flag overriding a single field
+pub const SET_FLAG: &str = "--set";
+
+/// A single `--set key.path=value` override
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    /// Dotted key path, starting with the plugin section (`redis.port`)
+    pub path: String,
+    /// Raw value, typed against the field it replaces
+    pub value: String,
+}
+
+impl Override {
+    /// Parse `key.path=value`
+    pub fn parse(raw: &str) -> ConfigResult<Self> {
+        let invalid = || {
+            TylError::configuration(format!(
+                "Invalid override `{raw}`: expected `section.key=value`"
+            ))
+        };
+
+        let (path, value) = raw.split_once('=').ok_or_else(invalid)?;
+        let path = path.trim();
+        let mut segments = path.split('.');
+        let has_field = segments.clone().count() >= 2;
+        if !has_field || segments.any(str::is_empty) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// The plugin section the override targets
+    pub fn section(&self) -> &str {
+        self.path.split('.').next().unwrap_or_default()
+    }
+
+    /// Apply the override to the serialized plugin `section`, whose fields
+    /// are described by `fields`
+    ///
+    /// The key must be serialized or declared in `fields` (unset `Option`
+    /// fields are only declared), and a number or boolean field only accepts
+    /// a value of that type.
+    pub fn apply(&self, section: &mut Value, fields: &[FieldMetadata]) -> ConfigResult<()> {
+        let keys: Vec<&str> = self.path.split('.').skip(1).collect();
+        if let Some(field) = keys.first() {
+            registry::declare_field(section, field, fields);
+        }
+        let mut target = section;
+        for key in &keys {
+            target = target
+                .get_mut(*key)
+                .ok_or_else(|| TylError::validation(&self.path, "unknown configuration key"))?;
+        }
+
+        // An unset field has no value to take the type from
+        let declared_type = match keys.as_slice() {
+            [name] if target.is_null() => fields
+                .iter()
+                .find(|field| field.name == *name)
+                .and_then(|field| field.value_type.as_deref()),
+            _ => None,
+        };
+        let shape = match declared_type {
+            Some("integer" | "number") => Value::from(0),
+            Some("boolean") => Value::from(false),
+            _ => target.clone(),
+        };
+
+        let expected = match &shape {
+            Value::Mapping(_) => {
+                return Err(TylError::validation(
+                    &self.path,
+                    "is a section, set one of its keys instead",
+                ))
+            }
+            Value::Number(_) => Some("a number"),
+            Value::Bool(_) => Some("a boolean"),
+            _ => None,
+        };
+
+        let value = yaml::coerce_scalar(self.value.clone(), &shape);
+        if let (Some(expected), Value::String(raw)) = (expected, &value) {
+            return Err(TylError::validation(
+                &self.path,
+                format!("expected {expected}, got `{raw}`"),
+            ));
+        }
+        *target = value;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Override {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.path, self.value)
+    }
+}
+
+#[cfg(feature = "clap")]
+fn parse_override(raw: &str) -> Result<Override, String> {
+    Override::parse(raw).map_err(|e| e.to_string())
+}
+
+/// Config files and overrides taken from the command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct ConfigArgs {
+    /// YAML config file, later files override earlier ones
+    #[cfg_attr(feature = "clap", arg(long = "config", value_name = "FILE"))]
+    pub config_files: Vec<PathBuf>,
+
+    /// Override a single value, e.g. `redis.port=6380`
+    #[cfg_attr(
+        feature = "clap",
+        arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)
+    )]
+    pub overrides: Vec<Override>,
+}
+
+impl ConfigArgs {
+    /// Collect `--config` and `--set` flags, in `--flag value` or
+    /// `--flag=value` form
+    ///
+    /// Other arguments are ignored so an application can keep its own flags
+    /// next to these.
+    pub fn parse_from<I, S>(args: I) -> ConfigResult<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg, None),
+            };
+            if flag != CONFIG_FLAG && flag != SET_FLAG {
+                continue;
+            }
+
+            let value = match inline_value {
+                Some(value) => value,
+                None => args.next().map(|v| v.as_ref().to_string()).ok_or_else(|| {
+                    TylError::configuration(format!("Missing value for `{flag}`"))
+                })?,
+            };
+            if flag == CONFIG_FLAG {
+                parsed.config_files.push(PathBuf::from(value));
+            } else {
+                parsed.overrides.push(Override::parse(&value)?);
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Collect the flags from the process arguments
+    pub fn from_process_args() -> ConfigResult<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flags_in_both_forms() {
+        let args = ConfigArgs::parse_from([
+            "--verbose",
+            "--config",
+            "base.yaml",
+            "--config=service.yaml",
+            "--set",
+            "redis.port=6380",
+            "--set=postgres.password=a=b",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            args.config_files,
+            vec![PathBuf::from("base.yaml"), PathBuf::from("service.yaml")]
+        );
+        assert_eq!(args.overrides[0].section(), "redis");
+        assert_eq!(args.overrides[0].value, "6380");
+        // Only the first `=` separates key and value
+        assert_eq!(args.overrides[1].path, "postgres.password");
+        assert_eq!(args.overrides[1].value, "a=b");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_overrides() {
+        assert!(ConfigArgs::parse_from(["--set", "redis"]).is_err());
+        assert!(ConfigArgs::parse_from(["--set", "redis=1"]).is_err());
+        assert!(ConfigArgs::parse_from(["--set", "redis..port=1"]).is_err());
+        assert!(ConfigArgs::parse_from(["--set"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_checks_field_types() {
+        let fields = vec![
+            FieldMetadata::new("password", "").value_type("string"),
+            FieldMetadata::new("retries", "").value_type("integer"),
+        ];
+        // `password` and `retries` are unset `Option`s, so not serialized
+        let mut section: Value = serde_yaml::from_str("host: localhost\nport: 6379\n").unwrap();
+
+        Override::parse("redis.port=6380")
+            .unwrap()
+            .apply(&mut section, &fields)
+            .unwrap();
+        Override::parse("redis.password=1234")
+            .unwrap()
+            .apply(&mut section, &fields)
+            .unwrap();
+        Override::parse("redis.retries=3")
+            .unwrap()
+            .apply(&mut section, &fields)
+            .unwrap();
+        assert_eq!(section["port"], Value::from(6380));
+        assert_eq!(section["password"], Value::from("1234"));
+        assert_eq!(section["retries"], Value::from(3));
+
+        let error = Override::parse("redis.port=abc")
+            .unwrap()
+            .apply(&mut section, &fields)
+            .unwrap_err();
+        assert!(error.to_string().contains("redis.port"));
+        assert!(error.to_string().contains("expected a number"));
+
+        let error = Override::parse("redis.prot=1")
+            .unwrap()
+            .apply(&mut section, &fields)
+            .unwrap_err();
+        assert!(error.to_string().contains("unknown configuration key"));
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn test_clap_flatten() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            config: ConfigArgs,
+        }
+
+        let cli =
+            Cli::try_parse_from(["svc", "--config", "a.yaml", "--set", "redis.port=1"]).unwrap();
+        assert_eq!(cli.config.config_files, vec![PathBuf::from("a.yaml")]);
+        assert_eq!(cli.config.overrides[0].path, "redis.port");
+
+        assert!(Cli::try_parse_from(["svc", "--set", "redis"]).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Audit trail of configuration versions
+//!
+//! A [`SharedConfig`](crate::SharedConfig) records every version it
+//! publishes: when, why ([`Trigger`]), what changed (a redacted
+//! [`ConfigDiff`]) and the fingerprint of the result. The first entry is the
+//! startup version, with every field reported as added. The history is
+//! bounded (oldest entries are dropped first) and can also be appended to a
+//! JSON-lines file, one [`AuditEntry`] per line:
+//!
+//! ```text
+//! {"version":2,"timestamp":"2026-10-18T09:12:44.031Z","trigger":"signal","fingerprint":"5f1c…","diff":{"changes":[…]}}
+//! ```
+
+use crate::{ConfigDiff, ConfigManager, ConfigResult};
+use serde::{Serialize, Serializer};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tyl_errors::TylError;
+
+/// Entries kept when no limit is set
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// What caused a new version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Startup,
+    /// A source file changed (see [`watch`](crate::watch))
+    FileChange,
+    /// SIGHUP (see `signal::reload_on_sighup`)
+    Signal,
+    /// A direct `store` / `reload` call
+    Api,
+}
+
+/// One published version
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    /// 1 for the startup version, then counting up
+    pub version: u64,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub timestamp: SystemTime,
+    pub trigger: Trigger,
+    pub fingerprint: String,
+    /// Changes from the previous version, secrets redacted
+    pub diff: ConfigDiff,
+}
+
+/// Bounded history, optionally mirrored to a JSON-lines file
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    limit: usize,
+    entries: VecDeque<AuditEntry>,
+    file: Option<PathBuf>,
+    /// Last version appended to a file
+    written: u64,
+    next_version: u64,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_HISTORY_LIMIT,
+            entries: VecDeque::new(),
+            file: None,
+            written: 0,
+            next_version: 1,
+        }
+    }
+}
+
+impl AuditLog {
+    pub(crate) fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        self.trim();
+    }
+
+    /// Append the kept entries not written to a file yet to `path`, then
+    /// every new one
+    pub(crate) fn set_file(&mut self, path: PathBuf) -> ConfigResult<()> {
+        for entry in &self.entries {
+            if entry.version > self.written {
+                append(&path, entry)?;
+                self.written = entry.version;
+            }
+        }
+        self.file = Some(path);
+        Ok(())
+    }
+
+    /// Record `new`; `diff` is relative to the previous version
+    ///
+    /// The entry is kept even if the audit file cannot be written; that error
+    /// is returned for the caller to report.
+    pub(crate) fn record(
+        &mut self,
+        trigger: Trigger,
+        new: &ConfigManager,
+        diff: ConfigDiff,
+    ) -> ConfigResult<()> {
+        let entry = AuditEntry {
+            version: self.next_version,
+            timestamp: SystemTime::now(),
+            trigger,
+            fingerprint: new.fingerprint()?,
+            diff,
+        };
+        self.next_version += 1;
+        let written = match &self.file {
+            Some(path) => append(path, &entry),
+            None => Ok(()),
+        };
+        if written.is_ok() && self.file.is_some() {
+            self.written = entry.version;
+        }
+        self.entries.push_back(entry);
+        self.trim();
+        written
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+}
+
+fn append(path: &PathBuf, entry: &AuditEntry) -> ConfigResult<()> {
+    let line = serde_json::to_string(entry)
+        .map_err(|e| TylError::serialization(format!("Failed to serialize audit entry: {e}")))?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{line}"))
+        .map_err(|e| {
+            TylError::configuration(format!(
+                "Failed to write audit file {}: {e}",
+                path.display()
+            ))
+        })
+}
+
+/// RFC 3339 in UTC with milliseconds
+fn serialize_timestamp<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_timestamp(*time))
+}
+
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(format_timestamp(time), "2024-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let config = ConfigManager::builder().build();
+        let mut log = AuditLog::default();
+        log.set_limit(2);
+        for trigger in [Trigger::Startup, Trigger::Api, Trigger::Signal] {
+            log.record(trigger, &config, ConfigDiff::default()).unwrap();
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].version, 2);
+        assert_eq!(entries[1].trigger, Trigger::Signal);
+    }
+
+    #[test]
+    fn test_file_gets_each_entry_once() {
+        let dir = std::env::temp_dir().join(format!("tyl-config-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+        let config = ConfigManager::builder().build();
+        let mut log = AuditLog::default();
+
+        log.record(Trigger::Startup, &config, ConfigDiff::default())
+            .unwrap();
+        log.set_file(path.clone()).unwrap();
+        log.set_file(path.clone()).unwrap();
+        log.record(Trigger::Api, &config, ConfigDiff::default())
+            .unwrap();
+        log.set_file(path.clone()).unwrap();
+
+        let versions: Vec<u64> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["version"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(versions, vec![1, 2]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,5 @@
+//! `tyl-config` command-line tool for the built-in plugins (see `tyl_config::cli`)
+
+fn main() {
+    std::process::exit(tyl_config::cli::Cli::default().main());
+}
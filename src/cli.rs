@@ -0,0 +1,534 @@
+//! `tyl-config` command-line tool
+//!
+//! ```text
+//! tyl-config docs [--format markdown|html] [--output FILE]
+//! tyl-config diff OLD.yaml NEW.yaml [--env FILE] [--format text|json]
+//! tyl-config lint CONFIG.yaml [--profile NAME] [--env FILE] [--format text|json]
+//! tyl-config policy POLICY.yaml CONFIG.yaml [--profile NAME] [--env FILE] [--format text|json]
+//! ```
+//!
+//! `diff` exits with 1 when the files differ, `lint` when it finds an error
+//! for the profile, and `policy` when an error-severity rule is violated, so a
+//! pipeline can gate on any of them.
+//!
+//! The commands look at the sections the config file or the `--env` snapshot
+//! configures; a registered plugin that neither mentions is left out.
+//!
+//! The bundled binary knows the built-in plugins. A service with custom
+//! plugins can ship the same tool by registering them on a [`Cli`]:
+//!
+//! ```rust,no_run
+//! use tyl_config::{cli::Cli, PostgresConfig};
+//!
+//! let cli = Cli::new(|builder| builder.with_postgres(PostgresConfig::default()));
+//! std::process::exit(cli.main());
+//! ```
+
+use crate::docs::DocFormat;
+use crate::policy::Policy;
+use crate::profile::Profile;
+use crate::{
+    dotenv, template, yaml, ConfigManager, ConfigManagerBuilder, ConfigResult, Environment, MapEnv,
+    PostgresConfig, RedisConfig,
+};
+use std::io::Write;
+use tyl_errors::TylError;
+
+/// Usage shown for `help` and unknown commands
+pub const USAGE: &str = "\
+Usage: tyl-config <command> [options]
+
+Commands:
+  docs [--format markdown|html] [--output FILE]
+      Reference of every configuration key, env var, type and default
+  diff OLD.yaml NEW.yaml [--env FILE] [--format text|json]
+      Changes between two config files, resolved under the env vars of a
+      .env-style snapshot (none by default); exits with 1 if they differ
+  lint CONFIG.yaml [--profile NAME] [--env FILE] [--format text|json]
+      Insecure and dev-only values, rated for the profile (default: the
+      snapshot's TYL_PROFILE, else development); exits with 1 on errors
+  policy POLICY.yaml CONFIG.yaml [--profile NAME] [--env FILE] [--format text|json]
+      Values breaking the rules of a policy file, for the profile chosen as
+      for lint; exits with 1 if an error rule is violated
+  help
+      Show this message
+";
+
+/// Adds the plugins a command works with to a builder
+pub type PluginSetup = Box<dyn Fn(ConfigManagerBuilder) -> ConfigManagerBuilder>;
+
+/// The command-line tool, configured with the plugins it knows about
+pub struct Cli {
+    plugins: PluginSetup,
+}
+
+impl Default for Cli {
+    /// The built-in plugins only
+    fn default() -> Self {
+        Self::new(|builder| {
+            builder
+                .with_postgres(PostgresConfig::default())
+                .with_redis(RedisConfig::default())
+        })
+    }
+}
+
+impl Cli {
+    /// `plugins` registers the plugins on a builder whose environment is
+    /// already set
+    pub fn new(plugins: impl Fn(ConfigManagerBuilder) -> ConfigManagerBuilder + 'static) -> Self {
+        Self {
+            plugins: Box::new(plugins),
+        }
+    }
+
+    /// Run with the process arguments, printing errors to stderr
+    ///
+    /// Returns the process exit code: 0 on success, 2 on errors.
+    pub fn main(&self) -> i32 {
+        let stdout = std::io::stdout();
+        match self.run(std::env::args().skip(1), &mut stdout.lock()) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("tyl-config: {e}");
+                2
+            }
+        }
+    }
+
+    /// Run one command, writing its output to `out`
+    pub fn run<I, S>(&self, args: I, out: &mut dyn Write) -> ConfigResult<i32>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args.into_iter().map(|a| a.as_ref().to_string()).collect();
+        let Some((command, rest)) = args.split_first() else {
+            write_out(out, USAGE)?;
+            return Ok(2);
+        };
+
+        match command.as_str() {
+            "docs" => self.docs(rest, out),
+            "diff" => self.diff(rest, out),
+            "lint" => self.lint(rest, out),
+            "policy" => self.policy(rest, out),
+            "help" | "--help" | "-h" => {
+                write_out(out, USAGE)?;
+                Ok(0)
+            }
+            other => Err(TylError::configuration(format!(
+                "Unknown command `{other}`\n\n{USAGE}"
+            ))),
+        }
+    }
+
+    /// A manager with every plugin at its defaults, for metadata
+    fn defaults(&self) -> ConfigManager {
+        (self.plugins)(ConfigManager::builder().with_environment(MapEnv::new())).build()
+    }
+
+    fn docs(&self, args: &[String], out: &mut dyn Write) -> ConfigResult<i32> {
+        let mut options = Options::parse(args, &["--format", "--output"])?;
+        let format = match options.take("--format") {
+            Some(format) => format.parse::<DocFormat>()?,
+            None => DocFormat::Markdown,
+        };
+        options.no_positionals()?;
+
+        let doc = self.defaults().reference_docs(format)?;
+        match options.take("--output") {
+            Some(path) => template::write_atomic(path, &doc)?,
+            None => write_out(out, &doc)?,
+        }
+        Ok(0)
+    }
+
+    fn diff(&self, args: &[String], out: &mut dyn Write) -> ConfigResult<i32> {
+        let mut options = Options::parse(args, &["--env", "--format"])?;
+        let json = options.json_format("diff")?;
+        let env = options.env()?;
+        let [old_path, new_path] = options.positionals.as_slice() else {
+            return Err(TylError::configuration(format!(
+                "`diff` needs two config files\n\n{USAGE}"
+            )));
+        };
+
+        let old = self.load(old_path, &env, None)?;
+        let new = self.load(new_path, &env, None)?;
+        let diff = old.diff(&new)?;
+
+        let report = if json {
+            diff.to_json()? + "\n"
+        } else {
+            diff.to_text()
+        };
+        write_out(out, &report)?;
+        Ok(if diff.is_empty() { 0 } else { 1 })
+    }
+
+    fn lint(&self, args: &[String], out: &mut dyn Write) -> ConfigResult<i32> {
+        let mut options = Options::parse(args, &["--profile", "--env", "--format"])?;
+        let json = options.json_format("lint")?;
+        let env = options.env()?;
+        let profile = options
+            .take("--profile")
+            .map(|name| name.parse::<Profile>())
+            .transpose()?;
+        let [path] = options.positionals.as_slice() else {
+            return Err(TylError::configuration(format!(
+                "`lint` needs one config file\n\n{USAGE}"
+            )));
+        };
+
+        let report = self.load(path, &env, profile)?.lint()?;
+        let text = if json {
+            report.to_json()? + "\n"
+        } else {
+            report.to_text()
+        };
+        write_out(out, &text)?;
+        Ok(if report.has_errors() { 1 } else { 0 })
+    }
+
+    fn policy(&self, args: &[String], out: &mut dyn Write) -> ConfigResult<i32> {
+        let mut options = Options::parse(args, &["--profile", "--env", "--format"])?;
+        let json = options.json_format("policy")?;
+        let env = options.env()?;
+        let profile = options
+            .take("--profile")
+            .map(|name| name.parse::<Profile>())
+            .transpose()?;
+        let [policy_path, path] = options.positionals.as_slice() else {
+            return Err(TylError::configuration(format!(
+                "`policy` needs a policy file and a config file\n\n{USAGE}"
+            )));
+        };
+
+        let policy = Policy::load(policy_path)?;
+        let report = policy.evaluate(&self.load(path, &env, profile)?)?;
+        let text = if json {
+            report.to_json()? + "\n"
+        } else {
+            report.to_text()
+        };
+        write_out(out, &text)?;
+        Ok(if report.has_errors() { 1 } else { 0 })
+    }
+
+    /// Resolve one YAML file with the registered plugins, failing on any error
+    ///
+    /// Only the sections the file or the env snapshot configures are kept, so
+    /// a plugin the service does not use is not reported at its defaults.
+    fn load(
+        &self,
+        path: &str,
+        env: &MapEnv,
+        profile: Option<Profile>,
+    ) -> ConfigResult<ConfigManager> {
+        if !std::path::Path::new(path).exists() {
+            return Err(TylError::configuration(format!(
+                "Config file not found: {path}"
+            )));
+        }
+        let mut builder = ConfigManager::builder().with_environment(env.clone());
+        if let Some(profile) = profile {
+            builder = builder.with_profile(profile);
+        }
+        let mut config = (self.plugins)(builder)
+            .with_yaml_file(path)?
+            .try_build()
+            .map_err(TylError::from)?;
+
+        let document = yaml::load_file(path)?;
+        let mut configured = Vec::new();
+        for name in config.section_names() {
+            let in_file = document.get(name).is_some();
+            let in_env = config.field_metadata(name)?.iter().any(|field| {
+                field
+                    .env_vars
+                    .iter()
+                    .chain(&field.deprecated_env)
+                    .any(|var| env.var(var).is_some())
+            });
+            if in_file || in_env {
+                configured.push(name);
+            }
+        }
+        config.retain_sections(|name| configured.contains(&name));
+        Ok(config)
+    }
+}
+
+/// `--flag value` / `--flag=value` options and positional arguments
+struct Options {
+    flags: Vec<(String, String)>,
+    positionals: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String], known: &[&str]) -> ConfigResult<Self> {
+        let mut flags = Vec::new();
+        let mut positionals = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positionals.push(arg.clone());
+                continue;
+            }
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), value.to_string()),
+                None => {
+                    let value = args.next().ok_or_else(|| {
+                        TylError::configuration(format!("Missing value for `{arg}`"))
+                    })?;
+                    (arg.clone(), value.clone())
+                }
+            };
+            if !known.contains(&flag.as_str()) {
+                return Err(TylError::configuration(format!(
+                    "Unknown option `{flag}`\n\n{USAGE}"
+                )));
+            }
+            flags.push((flag, value));
+        }
+        Ok(Self { flags, positionals })
+    }
+
+    /// The last value of `flag`
+    fn take(&mut self, flag: &str) -> Option<String> {
+        let value = self
+            .flags
+            .iter()
+            .rev()
+            .find(|(name, _)| name == flag)
+            .map(|(_, value)| value.clone());
+        self.flags.retain(|(name, _)| name != flag);
+        value
+    }
+
+    /// `--format text|json`, true for JSON
+    fn json_format(&mut self, command: &str) -> ConfigResult<bool> {
+        match self.take("--format").as_deref() {
+            None | Some("text") => Ok(false),
+            Some("json") => Ok(true),
+            Some(other) => Err(TylError::configuration(format!(
+                "Unknown {command} format `{other}`, expected `text` or `json`"
+            ))),
+        }
+    }
+
+    /// The `--env` snapshot, empty by default
+    fn env(&mut self) -> ConfigResult<MapEnv> {
+        match self.take("--env") {
+            Some(path) => dotenv::load(path, &MapEnv::new()),
+            None => Ok(MapEnv::new()),
+        }
+    }
+
+    fn no_positionals(&self) -> ConfigResult<()> {
+        match self.positionals.first() {
+            Some(extra) => Err(TylError::configuration(format!(
+                "Unexpected argument `{extra}`\n\n{USAGE}"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write_out(out: &mut dyn Write, text: &str) -> ConfigResult<()> {
+    out.write_all(text.as_bytes())
+        .map_err(|e| TylError::configuration(format!("Failed to write output: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cli: &Cli, args: &[&str]) -> (ConfigResult<i32>, String) {
+        let mut out = Vec::new();
+        let result = cli.run(args, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_docs_command() {
+        let (result, out) = run(&Cli::default(), &["docs"]);
+        assert_eq!(result.unwrap(), 0);
+        assert!(out.starts_with("# Configuration Reference"));
+        assert!(out.contains("`redis.port`"));
+
+        let (result, out) = run(&Cli::default(), &["docs", "--format=html"]);
+        assert_eq!(result.unwrap(), 0);
+        assert!(out.contains("<table>"));
+    }
+
+    #[test]
+    fn test_docs_only_registered_plugins() {
+        let cli = Cli::new(|builder| builder.with_redis(RedisConfig::default()));
+        let (_, out) = run(&cli, &["docs"]);
+        assert!(out.contains("`redis.host`"));
+        assert!(!out.contains("postgres"));
+    }
+
+    #[test]
+    fn test_diff_command() {
+        let dir =
+            std::env::temp_dir().join(format!("tyl-config-cli-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let redis = "redis:\n  host: cache\n  port: 6379\n  database: 0\n  pool_size: 5\n  timeout_seconds: 10\n";
+        std::fs::write(dir.join("old.yaml"), redis).unwrap();
+        std::fs::write(dir.join("new.yaml"), redis.replace("6379", "6380")).unwrap();
+        std::fs::write(dir.join("snapshot.env"), "TYL_REDIS_PASSWORD=hunter2\n").unwrap();
+        let old = dir.join("old.yaml").display().to_string();
+        let new = dir.join("new.yaml").display().to_string();
+        let env = dir.join("snapshot.env").display().to_string();
+
+        let (result, out) = run(&Cli::default(), &["diff", &old, &new]);
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(out, "redis:\n  ~ port: 6379 -> 6380\n");
+
+        let (result, out) = run(&Cli::default(), &["diff", &old, &old, "--env", &env]);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(out, "No changes\n");
+
+        let (result, out) = run(&Cli::default(), &["diff", "--format", "json", &old, &new]);
+        assert_eq!(result.unwrap(), 1);
+        let report: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(report["changes"][0]["kind"], "changed");
+
+        assert!(run(&Cli::default(), &["diff", &old]).0.is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_lint_command() {
+        let dir =
+            std::env::temp_dir().join(format!("tyl-config-cli-lint-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config.yaml");
+        let redis = "redis:\n  host: localhost\n  port: 6379\n  database: 0\n  pool_size: 5\n  timeout_seconds: 10\n";
+        std::fs::write(&config, redis).unwrap();
+        std::fs::write(dir.join("prod.env"), "TYL_PROFILE=production\n").unwrap();
+        let config = config.display().to_string();
+        let prod_env = dir.join("prod.env").display().to_string();
+        let cli = Cli::new(|builder| builder.with_redis(RedisConfig::default()));
+
+        let (result, out) = run(&cli, &["lint", &config]);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(
+            out,
+            "info: redis.host points at the local machine (localhost) [local_host]\n"
+        );
+
+        // The profile comes from the snapshot unless given
+        let (result, out) = run(&cli, &["lint", &config, "--env", &prod_env]);
+        assert_eq!(result.unwrap(), 1);
+        assert!(out.contains("error: redis.host points at the local machine"));
+        let (result, _) = run(
+            &cli,
+            &["lint", &config, "--env", &prod_env, "--profile", "dev"],
+        );
+        assert_eq!(result.unwrap(), 0);
+
+        let (_, out) = run(&cli, &["lint", &config, "--profile=prod", "--format=json"]);
+        let report: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(report["profile"], "production");
+        assert_eq!(report["findings"][0]["kind"], "local_host");
+
+        assert!(run(&cli, &["lint", &config, "--profile", "qa"]).0.is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_policy_command() {
+        let dir =
+            std::env::temp_dir().join(format!("tyl-config-cli-policy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let redis = "redis:\n  host: cache\n  port: 6379\n  database: 0\n  pool_size: 20\n  timeout_seconds: 10\n";
+        std::fs::write(dir.join("config.yaml"), redis).unwrap();
+        std::fs::write(
+            dir.join("policy.yaml"),
+            "rules:\n  - path: \"*.pool_size\"\n    max: 10\n    profiles: [production]\n  - path: redis.port\n    equals: 6380\n    severity: warning\n",
+        )
+        .unwrap();
+        let config = dir.join("config.yaml").display().to_string();
+        let policy = dir.join("policy.yaml").display().to_string();
+        let cli = Cli::new(|builder| builder.with_redis(RedisConfig::default()));
+
+        let (result, out) = run(&cli, &["policy", &policy, &config]);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(out, "warning: redis.port must be 6380\n");
+
+        let (result, out) = run(&cli, &["policy", &policy, &config, "--profile", "prod"]);
+        assert_eq!(result.unwrap(), 1);
+        assert!(out.starts_with("error: redis.pool_size must be at most 10\n"));
+
+        let (_, out) = run(
+            &cli,
+            &[
+                "policy",
+                &policy,
+                &config,
+                "--profile=prod",
+                "--format=json",
+            ],
+        );
+        let report: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(report["violations"][0]["rule"], "*.pool_size");
+
+        assert!(run(&cli, &["policy", &config]).0.is_err());
+        assert!(run(&cli, &["policy", &config, &config]).0.is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_commands_skip_unconfigured_sections() {
+        let dir =
+            std::env::temp_dir().join(format!("tyl-config-cli-sections-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let redis = "redis:\n  host: cache\n  port: 6379\n  database: 0\n  pool_size: 5\n  timeout_seconds: 10\n";
+        std::fs::write(dir.join("config.yaml"), redis).unwrap();
+        std::fs::write(dir.join("postgres.env"), "TYL_POSTGRES_HOST=db.internal\n").unwrap();
+        let config = dir.join("config.yaml").display().to_string();
+        let env = dir.join("postgres.env").display().to_string();
+
+        // The built-in postgres plugin is registered but not configured
+        let (result, out) = run(&Cli::default(), &["lint", &config, "--profile", "prod"]);
+        assert_eq!(result.unwrap(), 0);
+        assert!(!out.contains("postgres"), "{out}");
+
+        // An env var of the section configures it
+        let (_, out) = run(
+            &Cli::default(),
+            &["lint", &config, "--profile", "prod", "--env", &env],
+        );
+        assert!(out.contains("postgres."), "{out}");
+
+        // Nor do `postgres.*` policy rules apply to it
+        std::fs::write(
+            dir.join("policy.yaml"),
+            "rules:\n  - path: postgres.host\n    pattern: '\\.internal$'\n",
+        )
+        .unwrap();
+        let policy = dir.join("policy.yaml").display().to_string();
+        let (result, out) = run(&Cli::default(), &["policy", &policy, &config]);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(out, "No violations\n");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert!(run(&Cli::default(), &["frobnicate"]).0.is_err());
+        assert!(run(&Cli::default(), &["docs", "--colour", "red"])
+            .0
+            .is_err());
+        assert!(run(&Cli::default(), &["docs", "--format"]).0.is_err());
+        assert_eq!(run(&Cli::default(), &[]).0.unwrap(), 2);
+    }
+}
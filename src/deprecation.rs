@@ -0,0 +1,194 @@
+//! Deprecated YAML keys and env vars
+//!
+//! Plugins keep accepting old names through their field metadata:
+//!
+//! ```rust
+//! use tyl_config::FieldMetadata;
+//!
+//! FieldMetadata::new("database", "Database name")
+//!     .env(["TYL_POSTGRES_DATABASE"])
+//!     .deprecated_key("db_name")
+//!     .deprecated_env(["TYL_PG_DATABASE"]);
+//! ```
+//!
+//! Priority, highest first (`--set` overrides still win over all of them):
+//!
+//! 1. the field's env vars
+//! 2. its deprecated env vars, in the order declared
+//! 3. the YAML / directory key
+//! 4. its deprecated keys, in the order declared
+//!
+//! Every alias that is used produces a [`Deprecation`] naming the
+//! replacement. By default it is logged as a warning (log target
+//! `tyl_config::deprecation`) and kept on the manager
+//! (`ConfigManager::deprecations`); with
+//! `ConfigManagerBuilder::with_strict_deprecations` it is an error instead.
+
+use crate::{Environment, FieldMetadata};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::fmt;
+
+/// Log target of deprecation warnings
+pub const LOG_TARGET: &str = "tyl_config::deprecation";
+
+/// Where a deprecated name was used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasKind {
+    Key,
+    EnvVar,
+}
+
+/// A deprecated name that was used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Deprecation {
+    pub section: String,
+    pub field: String,
+    pub kind: AliasKind,
+    /// The deprecated key (`postgres.db_name`) or env var
+    pub alias: String,
+    /// What to use instead, same kind as `alias`
+    pub replacement: String,
+    /// Whether a higher-priority name was set too, so the alias was ignored
+    pub ignored: bool,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AliasKind::Key => "config key",
+            AliasKind::EnvVar => "env var",
+        };
+        write!(
+            f,
+            "{kind} `{}` is deprecated, use `{}` instead",
+            self.alias, self.replacement
+        )?;
+        if self.ignored {
+            f.write_str(" (ignored because the replacement is set)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Move deprecated keys of `values` to their field, the field's own key first
+pub(crate) fn rename_keys(
+    section: &str,
+    values: &mut Value,
+    fields: &[FieldMetadata],
+) -> Vec<Deprecation> {
+    let mut used = Vec::new();
+    let Value::Mapping(values) = values else {
+        return used;
+    };
+    for field in fields {
+        for alias in &field.deprecated_keys {
+            let Some(value) = values.remove(alias.as_str()) else {
+                continue;
+            };
+            let ignored = values.contains_key(field.name.as_str());
+            if !ignored {
+                values.insert(Value::from(field.name.as_str()), value);
+            }
+            used.push(Deprecation {
+                section: section.to_string(),
+                field: field.name.clone(),
+                kind: AliasKind::Key,
+                alias: format!("{section}.{alias}"),
+                replacement: format!("{section}.{}", field.name),
+                ignored,
+            });
+        }
+    }
+    used
+}
+
+/// Values of deprecated env vars for fields none of whose env vars are set
+///
+/// Returns `(field, raw value)` pairs to apply and every alias that is set.
+pub(crate) fn env_values(
+    section: &str,
+    fields: &[FieldMetadata],
+    env: &dyn Environment,
+) -> (Vec<(String, String)>, Vec<Deprecation>) {
+    let mut values = Vec::new();
+    let mut used = Vec::new();
+    for field in fields {
+        let mut ignored = field.env_vars.iter().any(|var| env.var(var).is_some());
+        for alias in &field.deprecated_env {
+            let Some(value) = env.var(alias) else {
+                continue;
+            };
+            if !ignored {
+                values.push((field.name.clone(), value));
+            }
+            used.push(Deprecation {
+                section: section.to_string(),
+                field: field.name.clone(),
+                kind: AliasKind::EnvVar,
+                alias: alias.clone(),
+                replacement: field
+                    .env_vars
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| format!("{section}.{}", field.name)),
+                ignored,
+            });
+            ignored = true;
+        }
+    }
+    (values, used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MapEnv;
+
+    fn fields() -> Vec<FieldMetadata> {
+        vec![FieldMetadata::new("database", "")
+            .env(["TYL_POSTGRES_DATABASE"])
+            .deprecated_key("db_name")
+            .deprecated_env(["TYL_PG_DATABASE", "PG_DB"])]
+    }
+
+    #[test]
+    fn test_rename_keys() {
+        let mut values: Value = serde_yaml::from_str("db_name: legacy\nhost: h\n").unwrap();
+        let used = rename_keys("postgres", &mut values, &fields());
+        assert_eq!(
+            values,
+            serde_yaml::from_str::<Value>("host: h\ndatabase: legacy\n").unwrap()
+        );
+        assert_eq!(
+            used[0].to_string(),
+            "config key `postgres.db_name` is deprecated, use `postgres.database` instead"
+        );
+
+        let mut both: Value = serde_yaml::from_str("db_name: legacy\ndatabase: new\n").unwrap();
+        let used = rename_keys("postgres", &mut both, &fields());
+        assert_eq!(
+            both,
+            serde_yaml::from_str::<Value>("database: new\n").unwrap()
+        );
+        assert!(used[0].ignored);
+    }
+
+    #[test]
+    fn test_env_values_priority() {
+        let env = MapEnv::new()
+            .with("TYL_PG_DATABASE", "first")
+            .with("PG_DB", "second");
+        let (values, used) = env_values("postgres", &fields(), &env);
+        assert_eq!(values, vec![("database".to_string(), "first".to_string())]);
+        assert_eq!(used.len(), 2);
+        assert_eq!(used[0].replacement, "TYL_POSTGRES_DATABASE");
+        assert!(!used[0].ignored && used[1].ignored);
+
+        let env = env.with("TYL_POSTGRES_DATABASE", "current");
+        let (values, used) = env_values("postgres", &fields(), &env);
+        assert!(values.is_empty());
+        assert!(used.iter().all(|d| d.ignored));
+    }
+}
@@ -0,0 +1,314 @@
+//! Configuration diffs
+//!
+//! `ConfigManager::diff` compares two resolved configurations field by field.
+//! Nested mappings are compared key by key, every other value as a whole.
+//! Values of secret fields (see [`FieldMetadata::secret`]) are replaced by
+//! [`REDACTED`]: the diff says that a password changed, never what to.
+//!
+//! [`FieldMetadata::secret`]: crate::FieldMetadata::secret
+
+use crate::ConfigResult;
+use serde::Serialize;
+use serde_json::Value;
+use tyl_errors::TylError;
+
+/// Stands in for the value of a secret field
+pub const REDACTED: &str = "<redacted>";
+
+/// How a field differs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only set in the new configuration
+    Added,
+    /// Only set in the old configuration
+    Removed,
+    /// Set in both, to different values
+    Changed,
+}
+
+/// One differing field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// Plugin section (`redis`)
+    pub section: String,
+    /// Dotted key within the section (`port`, `tls.ca_file`)
+    pub key: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// Differences between two configurations, grouped by plugin in section order
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl ConfigDiff {
+    /// Compare `(section, values)` lists; `is_secret(section, field)` decides
+    /// which values are redacted
+    pub fn compute(
+        old: &[(&str, serde_yaml::Value)],
+        new: &[(&str, serde_yaml::Value)],
+        is_secret: impl Fn(&str, &str) -> bool,
+    ) -> ConfigResult<Self> {
+        let mut sections: Vec<&str> = old.iter().map(|(name, _)| *name).collect();
+        for (name, _) in new {
+            if !sections.contains(name) {
+                sections.push(name);
+            }
+        }
+
+        let lookup = |list: &[(&str, serde_yaml::Value)], name: &str| -> ConfigResult<Value> {
+            match list.iter().find(|(section, _)| *section == name) {
+                Some((_, value)) => serde_json::to_value(value).map_err(|e| {
+                    TylError::serialization(format!("Failed to serialize {name} config: {e}"))
+                }),
+                None => Ok(Value::Null),
+            }
+        };
+
+        let mut diff = Self::default();
+        for section in sections {
+            let mut changes = Vec::new();
+            compare(
+                &lookup(old, section)?,
+                &lookup(new, section)?,
+                "",
+                &mut changes,
+            );
+            for (key, kind, old, new) in changes {
+                let field = key.split('.').next().unwrap_or_default();
+                let secret = is_secret(section, field);
+                let redact = |value: Option<Value>| {
+                    if secret {
+                        value.map(|_| Value::String(REDACTED.to_string()))
+                    } else {
+                        value
+                    }
+                };
+                diff.changes.push(FieldChange {
+                    section: section.to_string(),
+                    key,
+                    kind,
+                    old: redact(old),
+                    new: redact(new),
+                });
+            }
+        }
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Changes to one plugin
+    pub fn section<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a FieldChange> {
+        self.changes
+            .iter()
+            .filter(move |change| change.section == name)
+    }
+
+    /// A diff with only the changes to one plugin
+    pub fn for_section(&self, name: &str) -> ConfigDiff {
+        ConfigDiff {
+            changes: self.section(name).cloned().collect(),
+        }
+    }
+
+    /// The diff from new back to old
+    pub fn reversed(&self) -> ConfigDiff {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| FieldChange {
+                kind: match change.kind {
+                    ChangeKind::Added => ChangeKind::Removed,
+                    ChangeKind::Removed => ChangeKind::Added,
+                    ChangeKind::Changed => ChangeKind::Changed,
+                },
+                old: change.new.clone(),
+                new: change.old.clone(),
+                ..change.clone()
+            })
+            .collect();
+        ConfigDiff { changes }
+    }
+
+    /// Human-readable report, one line per field grouped by plugin
+    pub fn to_text(&self) -> String {
+        if self.is_empty() {
+            return "No changes\n".to_string();
+        }
+
+        let mut out = String::new();
+        let mut current = None;
+        for change in &self.changes {
+            if current != Some(change.section.as_str()) {
+                out.push_str(&format!("{}:\n", change.section));
+                current = Some(change.section.as_str());
+            }
+            let show = |value: &Option<Value>| match value {
+                Some(Value::String(s)) if s == REDACTED => s.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            };
+            let line = match change.kind {
+                ChangeKind::Added => format!("  + {}: {}", change.key, show(&change.new)),
+                ChangeKind::Removed => format!("  - {}: {}", change.key, show(&change.old)),
+                ChangeKind::Changed => format!(
+                    "  ~ {}: {} -> {}",
+                    change.key,
+                    show(&change.old),
+                    show(&change.new)
+                ),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Machine-readable report
+    pub fn to_json(&self) -> ConfigResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| TylError::serialization(format!("Failed to serialize diff: {e}")))
+    }
+}
+
+type RawChange = (String, ChangeKind, Option<Value>, Option<Value>);
+
+fn compare(old: &Value, new: &Value, prefix: &str, changes: &mut Vec<RawChange>) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{prefix}.{name}")
+        }
+    };
+
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (name, old_value) in old_map {
+                match new_map.get(name) {
+                    Some(new_value) => compare(old_value, new_value, &key(name), changes),
+                    None => removed(old_value, &key(name), changes),
+                }
+            }
+            for (name, new_value) in new_map {
+                if !old_map.contains_key(name) {
+                    added(new_value, &key(name), changes);
+                }
+            }
+        }
+        (Value::Null, Value::Null) => {}
+        (Value::Null, new) => added(new, prefix, changes),
+        (old, Value::Null) => removed(old, prefix, changes),
+        (old, new) if old != new => changes.push((
+            prefix.to_string(),
+            ChangeKind::Changed,
+            Some(old.clone()),
+            Some(new.clone()),
+        )),
+        _ => {}
+    }
+}
+
+fn added(value: &Value, path: &str, changes: &mut Vec<RawChange>) {
+    compare_side(value, path, changes, ChangeKind::Added)
+}
+
+fn removed(value: &Value, path: &str, changes: &mut Vec<RawChange>) {
+    compare_side(value, path, changes, ChangeKind::Removed)
+}
+
+/// Report every leaf of `value` as added or removed
+fn compare_side(value: &Value, path: &str, changes: &mut Vec<RawChange>, kind: ChangeKind) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (name, child) in map {
+                let child_path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                compare_side(child, &child_path, changes, kind);
+            }
+        }
+        _ => {
+            let value = Some(value.clone());
+            let (old, new) = match kind {
+                ChangeKind::Removed => (value, None),
+                _ => (None, value),
+            };
+            changes.push((path.to_string(), kind, old, new));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_added_removed_changed_per_section() {
+        let old = [
+            ("redis", yaml("host: a\nport: 6379\ntls:\n  verify: true\n")),
+            ("legacy", yaml("enabled: true\n")),
+        ];
+        let new = [
+            (
+                "redis",
+                yaml("host: b\nport: 6379\npassword: hunter2\ntls:\n  verify: false\n"),
+            ),
+            ("api", yaml("timeout_ms: 500\n")),
+        ];
+
+        let diff = ConfigDiff::compute(&old, &new, |_, field| field == "password").unwrap();
+        let summary: Vec<(&str, &str, ChangeKind)> = diff
+            .changes
+            .iter()
+            .map(|c| (c.section.as_str(), c.key.as_str(), c.kind))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("redis", "host", ChangeKind::Changed),
+                ("redis", "tls.verify", ChangeKind::Changed),
+                ("redis", "password", ChangeKind::Added),
+                ("legacy", "enabled", ChangeKind::Removed),
+                ("api", "timeout_ms", ChangeKind::Added),
+            ]
+        );
+        assert_eq!(diff.changes[2].new, Some(Value::from(REDACTED)));
+        assert!(!diff.to_text().contains("hunter2"));
+        assert!(!diff.to_json().unwrap().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_text_report() {
+        let old = [("redis", yaml("host: a\nport: 6379\n"))];
+        let new = [("redis", yaml("host: b\nport: 6379\n"))];
+
+        let diff = ConfigDiff::compute(&old, &new, |_, _| false).unwrap();
+        assert_eq!(diff.to_text(), "redis:\n  ~ host: \"a\" -> \"b\"\n");
+
+        let back = diff.reversed();
+        assert_eq!(back.to_text(), "redis:\n  ~ host: \"b\" -> \"a\"\n");
+        assert_eq!(back.reversed(), diff);
+        assert!(diff.for_section("postgres").is_empty());
+
+        let same = ConfigDiff::compute(&old, &old, |_, _| false).unwrap();
+        assert!(same.is_empty());
+        assert_eq!(same.to_text(), "No changes\n");
+    }
+}
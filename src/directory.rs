@@ -0,0 +1,174 @@
+//! Key-per-file directory source (Kubernetes ConfigMaps and Secrets)
+//!
+//! Kubernetes mounts a ConfigMap or Secret as a directory with one file per
+//! key. A file name is a dotted key path (`postgres.host`), and nested
+//! directories are accepted as well (`postgres/host`):
+//!
+//! ```text
+//! /etc/config/
+//! ├── ..2024_05_01_10_00_00.123/   <- real files, one snapshot per update
+//! ├── ..data -> ..2024_05_01_10_00_00.123
+//! ├── postgres.host -> ..data/postgres.host
+//! └── postgres.password -> ..data/postgres.password
+//! ```
+//!
+//! Kubernetes updates the mount atomically by swapping the `..data` symlink.
+//! When `..data` exists it is resolved once and every file is read from that
+//! snapshot, so a load that races an update never mixes old and new values.
+//! Entries starting with `.` are skipped. One trailing newline is removed from
+//! each value.
+
+use crate::ConfigResult;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+use tyl_errors::TylError;
+
+/// Symlink Kubernetes swaps to publish a new snapshot
+pub const DATA_LINK: &str = "..data";
+
+/// Read a key-per-file directory into a YAML mapping of string values
+pub fn load_dir(path: impl AsRef<Path>) -> ConfigResult<Value> {
+    let root = snapshot_root(path.as_ref())?;
+    let mut tree = Value::Mapping(Mapping::new());
+    read_into(&root, &[], &mut tree)?;
+    Ok(tree)
+}
+
+/// The directory to read: `..data` resolved once when present, `path` otherwise
+fn snapshot_root(path: &Path) -> ConfigResult<PathBuf> {
+    let data_link = path.join(DATA_LINK);
+    let root = if data_link.exists() {
+        data_link.canonicalize()
+    } else {
+        path.canonicalize()
+    };
+    root.map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config directory {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+fn read_into(dir: &Path, prefix: &[String], tree: &mut Value) -> ConfigResult<()> {
+    let read_error = |e: std::io::Error| {
+        TylError::configuration(format!(
+            "Failed to read config directory {}: {e}",
+            dir.display()
+        ))
+    };
+
+    let mut entries = std::fs::read_dir(dir)
+        .map_err(read_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        let mut key_path = prefix.to_vec();
+        key_path.extend(file_name.split('.').map(str::to_string));
+
+        // `metadata` follows symlinks, which is how Kubernetes exposes keys
+        let path = entry.path();
+        let metadata = std::fs::metadata(&path).map_err(read_error)?;
+        if metadata.is_dir() {
+            read_into(&path, &key_path, tree)?;
+        } else {
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                TylError::configuration(format!(
+                    "Failed to read config key {}: {e}",
+                    path.display()
+                ))
+            })?;
+            let content = content
+                .strip_suffix('\n')
+                .map(|c| c.strip_suffix('\r').unwrap_or(c))
+                .unwrap_or(&content);
+            insert_path(tree, &key_path, Value::String(content.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn insert_path(tree: &mut Value, key_path: &[String], value: Value) {
+    let Some((last, parents)) = key_path.split_last() else {
+        return;
+    };
+
+    let mut node = tree;
+    for key in parents {
+        if !node.is_mapping() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        let Value::Mapping(map) = node else {
+            unreachable!("node was just made a mapping");
+        };
+        node = map
+            .entry(Value::String(key.clone()))
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+    }
+    if let Value::Mapping(map) = node {
+        map.insert(Value::String(last.clone()), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tyl-config-{name}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_dotted_and_nested_keys() {
+        let dir = temp_dir("dir-keys");
+        std::fs::write(dir.join("postgres.host"), "db.internal\n").unwrap();
+        std::fs::create_dir_all(dir.join("redis")).unwrap();
+        std::fs::write(dir.join("redis/port"), "6380").unwrap();
+        std::fs::write(dir.join(".hidden"), "ignored").unwrap();
+
+        let tree = load_dir(&dir).unwrap();
+
+        assert_eq!(tree["postgres"]["host"], Value::from("db.internal"));
+        assert_eq!(tree["redis"]["port"], Value::from("6380"));
+        assert!(tree.get(".hidden").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kubernetes_data_symlink_layout() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("dir-k8s");
+        let snapshot = dir.join("..2024_05_01_10_00_00.123");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("postgres.host"), "snapshot-host").unwrap();
+        symlink("..2024_05_01_10_00_00.123", dir.join(DATA_LINK)).unwrap();
+        symlink("..data/postgres.host", dir.join("postgres.host")).unwrap();
+
+        let tree = load_dir(&dir).unwrap();
+        assert_eq!(tree["postgres"]["host"], Value::from("snapshot-host"));
+        // Only the published keys, no entries for the hidden snapshot dirs
+        assert_eq!(tree.as_mapping().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_directory_fails() {
+        let error = load_dir("/nonexistent/tyl-config-dir").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Failed to read config directory"));
+    }
+}
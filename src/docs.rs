@@ -0,0 +1,202 @@
+//! Configuration reference documents
+//!
+//! Renders the field metadata of every configured plugin as a reference
+//! table: YAML key, env var chain, type, default and whether the value is a
+//! secret. Generated from the same metadata as the config template, so
+//! runbooks stop drifting from the code.
+//!
+//! ```rust
+//! use tyl_config::{docs::DocFormat, ConfigManager, MapEnv, PostgresConfig};
+//!
+//! let config = ConfigManager::builder()
+//!     .with_environment(MapEnv::new())
+//!     .with_postgres(PostgresConfig::default())
+//!     .build();
+//!
+//! let markdown = config.reference_docs(DocFormat::Markdown).unwrap();
+//! assert!(markdown.contains("| `postgres.host` | `TYL_POSTGRES_HOST`, `PGHOST` |"));
+//! ```
+
+use crate::{ConfigResult, FieldMetadata};
+use tyl_errors::TylError;
+
+/// Shown instead of the default of a secret field
+const SECRET_DEFAULT: &str = "(secret)";
+
+/// Output format of a reference document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl std::str::FromStr for DocFormat {
+    type Err = TylError;
+
+    fn from_str(s: &str) -> ConfigResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            other => Err(TylError::configuration(format!(
+                "Unknown doc format `{other}`, expected `markdown` or `html`"
+            ))),
+        }
+    }
+}
+
+/// One row of the reference table, with cells already formatted as text
+struct Row {
+    key: String,
+    env_vars: Vec<String>,
+    value_type: String,
+    default: Option<String>,
+    secret: bool,
+    description: String,
+}
+
+impl Row {
+    fn new(section: &str, field: &FieldMetadata) -> Self {
+        let mut value_type = field.value_type.clone().unwrap_or_default();
+        if let Some(unit) = &field.unit {
+            value_type = format!("{value_type} ({unit})").trim_start().to_string();
+        }
+        let default = if field.secret && field.default.is_some() {
+            Some(SECRET_DEFAULT.to_string())
+        } else {
+            field.default.clone()
+        };
+
+        Self {
+            key: format!("{section}.{}", field.name),
+            env_vars: field.env_vars.clone(),
+            value_type,
+            default,
+            secret: field.secret,
+            description: field.description.clone(),
+        }
+    }
+}
+
+/// Render a reference document for `sections` (plugin name, field metadata)
+pub fn render(sections: &[(&str, Vec<FieldMetadata>)], format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => render_markdown(sections),
+        DocFormat::Html => render_html(sections),
+    }
+}
+
+const HEADERS: [&str; 6] = [
+    "YAML key",
+    "Env vars (highest priority first)",
+    "Type",
+    "Default",
+    "Secret",
+    "Description",
+];
+
+fn render_markdown(sections: &[(&str, Vec<FieldMetadata>)]) -> String {
+    let code = |text: &str| format!("`{}`", text.replace('`', "'"));
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+
+    let mut out = String::from("# Configuration Reference\n");
+    for (section, fields) in sections {
+        out.push_str(&format!("\n## `{section}`\n\n"));
+        out.push_str(&format!("| {} |\n", HEADERS.join(" | ")));
+        out.push_str(&format!("|{}\n", "---|".repeat(HEADERS.len())));
+        for field in fields {
+            let row = Row::new(section, field);
+            let env_vars: Vec<String> = row.env_vars.iter().map(|var| code(var)).collect();
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                code(&row.key),
+                env_vars.join(", "),
+                cell(&row.value_type),
+                row.default.as_deref().map(code).unwrap_or_default(),
+                if row.secret { "yes" } else { "no" },
+                cell(&row.description),
+            ));
+        }
+    }
+    out
+}
+
+fn render_html(sections: &[(&str, Vec<FieldMetadata>)]) -> String {
+    let code = |text: &str| format!("<code>{}</code>", escape_html(text));
+
+    let mut out = String::from("<h1>Configuration Reference</h1>\n");
+    for (section, fields) in sections {
+        out.push_str(&format!("<h2>{}</h2>\n<table>\n<thead><tr>", code(section)));
+        for header in HEADERS {
+            out.push_str(&format!("<th>{header}</th>"));
+        }
+        out.push_str("</tr></thead>\n<tbody>\n");
+        for field in fields {
+            let row = Row::new(section, field);
+            let env_vars: Vec<String> = row.env_vars.iter().map(|var| code(var)).collect();
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                code(&row.key),
+                env_vars.join(", "),
+                escape_html(&row.value_type),
+                row.default.as_deref().map(code).unwrap_or_default(),
+                if row.secret { "yes" } else { "no" },
+                escape_html(&row.description),
+            ));
+        }
+        out.push_str("</tbody>\n</table>\n");
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<FieldMetadata> {
+        vec![
+            FieldMetadata::new("timeout_seconds", "Connection timeout")
+                .env(["TYL_API_TIMEOUT_SECONDS"])
+                .value_type("integer")
+                .unit("seconds")
+                .default_value("30"),
+            FieldMetadata::new("token", "Token <with | pipe>")
+                .env(["TYL_API_TOKEN", "API_TOKEN"])
+                .value_type("string")
+                .default_value("dev-token")
+                .secret(),
+        ]
+    }
+
+    #[test]
+    fn test_markdown_reference() {
+        let doc = render(&[("api", fields())], DocFormat::Markdown);
+
+        assert!(doc.contains("## `api`"));
+        assert!(doc.contains(
+            "| `api.timeout_seconds` | `TYL_API_TIMEOUT_SECONDS` | integer (seconds) | `30` | no | Connection timeout |"
+        ));
+        assert!(doc.contains(
+            "| `api.token` | `TYL_API_TOKEN`, `API_TOKEN` | string | `(secret)` | yes | Token <with \\| pipe> |"
+        ));
+        assert!(!doc.contains("dev-token"));
+    }
+
+    #[test]
+    fn test_html_reference_escapes() {
+        let doc = render(&[("api", fields())], DocFormat::Html);
+
+        assert!(doc.contains("<h2><code>api</code></h2>"));
+        assert!(doc.contains("Token &lt;with | pipe&gt;"));
+        assert!(!doc.contains("dev-token"));
+        assert_eq!("HTML".parse::<DocFormat>().unwrap(), DocFormat::Html);
+        assert!("pdf".parse::<DocFormat>().is_err());
+    }
+}
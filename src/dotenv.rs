@@ -0,0 +1,344 @@
+//! `.env` file parsing
+//!
+//! Local development keeps secrets and overrides in a `.env` file. It is
+//! parsed into a [`MapEnv`] and layered *underneath* the environment the
+//! builder already uses, so real env vars keep priority and `std::env` is
+//! never modified.
+//!
+//! Supported syntax:
+//!
+//! ```text
+//! # comment
+//! export TYL_POSTGRES_HOST=localhost      # `export` prefix and inline comments
+//! TYL_POSTGRES_PASSWORD='literal $value'  # single quotes: no escapes, no expansion
+//! TYL_REDIS_URL="redis://${REDIS_HOST:-localhost}:6379"
+//! TLS_CERT="-----BEGIN CERTIFICATE-----
+//! MIIB...
+//! -----END CERTIFICATE-----"              # double quotes: multi-line, \n \t \" \\ \$
+//! ```
+//!
+//! `$VAR`, `${VAR}` and `${VAR:-default}` expand to the real environment first,
+//! then to variables defined earlier in the file, and to an empty string
+//! otherwise.
+
+use crate::{ConfigResult, Environment, MapEnv};
+use std::path::Path;
+use tyl_errors::TylError;
+
+/// Read and parse a `.env` file, expanding references against `base`
+pub fn load(path: impl AsRef<Path>, base: &dyn Environment) -> ConfigResult<MapEnv> {
+    let path = path.as_ref();
+    parse_file(path, &read(path)?, base)
+}
+
+/// Content of a `.env` file
+pub(crate) fn read(path: &Path) -> ConfigResult<String> {
+    std::fs::read_to_string(path).map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read dotenv file {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+/// `parse`, with `path` in the error
+pub(crate) fn parse_file(
+    path: &Path,
+    content: &str,
+    base: &dyn Environment,
+) -> ConfigResult<MapEnv> {
+    parse(content, base).map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to parse dotenv file {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+/// Parse `.env` content, expanding references against `base`
+pub fn parse(content: &str, base: &dyn Environment) -> ConfigResult<MapEnv> {
+    let mut parser = Parser {
+        chars: content.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut vars = MapEnv::new();
+
+    while let Some((key, raw)) = parser.next_entry()? {
+        let value = match raw {
+            RawValue::Literal(value) => value,
+            RawValue::Expand { text, escapes } => expand(&text, escapes, base, &vars),
+        };
+        vars.set(key, value);
+    }
+    Ok(vars)
+}
+
+enum RawValue {
+    Literal(String),
+    Expand { text: String, escapes: bool },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> TylError {
+        TylError::configuration(format!("line {}: {message}", self.line))
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.bump();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn next_entry(&mut self) -> ConfigResult<Option<(String, RawValue)>> {
+        loop {
+            self.skip_inline_whitespace();
+            match self.peek() {
+                None => return Ok(None),
+                Some('\n') | Some('#') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        let mut key = self.read_key();
+        if key == "export" && matches!(self.peek(), Some(' ' | '\t')) {
+            self.skip_inline_whitespace();
+            key = self.read_key();
+        }
+        if key.is_empty() {
+            return Err(self.error("expected a variable name"));
+        }
+        if key.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error(&format!("invalid variable name `{key}`")));
+        }
+
+        self.skip_inline_whitespace();
+        if self.bump() != Some('=') {
+            return Err(self.error(&format!("expected `=` after `{key}`")));
+        }
+        self.skip_inline_whitespace();
+
+        let value = match self.peek() {
+            Some('\'') => {
+                self.bump();
+                RawValue::Literal(self.read_quoted('\'')?)
+            }
+            Some('"') => {
+                self.bump();
+                RawValue::Expand {
+                    text: self.read_quoted('"')?,
+                    escapes: true,
+                }
+            }
+            _ => RawValue::Expand {
+                text: self.read_unquoted(),
+                escapes: false,
+            },
+        };
+
+        // Only a comment may follow the value
+        self.skip_inline_whitespace();
+        match self.peek() {
+            None | Some('\n') | Some('#') => self.skip_line(),
+            Some(_) => return Err(self.error("unexpected characters after value")),
+        }
+        Ok(Some((key, value)))
+    }
+
+    fn read_key(&mut self) -> String {
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                key.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        key
+    }
+
+    /// Read up to the closing quote, keeping escape sequences for `expand`
+    fn read_quoted(&mut self, quote: char) -> ConfigResult<String> {
+        let start_line = self.line;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None => {
+                    return Err(TylError::configuration(format!(
+                        "line {start_line}: unterminated {quote} quote"
+                    )))
+                }
+                Some(c) if c == quote => return Ok(text),
+                Some('\\') if quote == '"' => {
+                    text.push('\\');
+                    if let Some(escaped) = self.bump() {
+                        text.push(escaped);
+                    }
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn read_unquoted(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            // `#` starts a comment only after whitespace: `a#b` is a value
+            if c == '\n' || (c == '#' && text.ends_with([' ', '\t'])) {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        text.trim_end().to_string()
+    }
+}
+
+/// Apply escapes (double-quoted values only) and `$VAR` expansion
+fn expand(text: &str, escapes: bool, base: &dyn Environment, defined: &MapEnv) -> String {
+    let lookup = |name: &str| base.var(name).or_else(|| defined.var(name));
+
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if escapes => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut reference = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    reference.push(c);
+                }
+                let value = match reference.split_once(":-") {
+                    Some((name, default)) => lookup(name)
+                        .filter(|value| !value.is_empty())
+                        .unwrap_or_else(|| default.to_string()),
+                    None => lookup(&reference).unwrap_or_default(),
+                };
+                out.push_str(&value);
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                out.push_str(&lookup(&name).unwrap_or_default());
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic_syntax() {
+        let content = r#"
+# Local development
+export TYL_POSTGRES_HOST=localhost   # inline comment
+TYL_POSTGRES_PASSWORD='p@ss $NOT_EXPANDED # kept'
+TYL_POSTGRES_DATABASE = app_dev
+URL_WITH_HASH=http://host/#anchor
+EMPTY=
+"#;
+        let env = parse(content, &MapEnv::new()).unwrap();
+
+        assert_eq!(env.var("TYL_POSTGRES_HOST").as_deref(), Some("localhost"));
+        assert_eq!(
+            env.var("TYL_POSTGRES_PASSWORD").as_deref(),
+            Some("p@ss $NOT_EXPANDED # kept")
+        );
+        assert_eq!(env.var("TYL_POSTGRES_DATABASE").as_deref(), Some("app_dev"));
+        assert_eq!(
+            env.var("URL_WITH_HASH").as_deref(),
+            Some("http://host/#anchor")
+        );
+        assert_eq!(env.var("EMPTY").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_parse_double_quotes_multiline_and_escapes() {
+        let content =
+            "CERT=\"line one\nline two\"\nESCAPED=\"tab\\there \\\"quoted\\\" \\$HOME\"\n";
+        let env = parse(content, &MapEnv::new().with("HOME", "/root")).unwrap();
+
+        assert_eq!(env.var("CERT").as_deref(), Some("line one\nline two"));
+        assert_eq!(
+            env.var("ESCAPED").as_deref(),
+            Some("tab\there \"quoted\" $HOME")
+        );
+    }
+
+    #[test]
+    fn test_expansion_prefers_real_environment() {
+        let content = r#"
+REDIS_HOST=dotenv-redis
+REDIS_PORT=6380
+TYL_REDIS_URL="redis://${REDIS_HOST}:$REDIS_PORT/${REDIS_DB:-0}"
+"#;
+        let base = MapEnv::new().with("REDIS_HOST", "real-redis");
+        let env = parse(content, &base).unwrap();
+
+        assert_eq!(
+            env.var("TYL_REDIS_URL").as_deref(),
+            Some("redis://real-redis:6380/0")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        let error = parse("GOOD=1\nBAD LINE\n", &MapEnv::new()).unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+
+        let error = parse("OPEN=\"never closed\n", &MapEnv::new()).unwrap_err();
+        assert!(error.to_string().contains("unterminated"));
+    }
+}
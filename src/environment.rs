@@ -0,0 +1,145 @@
+//! Environment variable sources
+//!
+//! Plugins never read `std::env` directly. They receive an [`Environment`] so
+//! the same loading code can run against the real process environment or a
+//! fixed in-memory snapshot (tests, embedders, tooling).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Port (Interface) - read-only view of environment variables
+pub trait Environment: std::fmt::Debug + Send + Sync {
+    /// Get the value of a variable, `None` if it is not set
+    fn var(&self, key: &str) -> Option<String>;
+}
+
+/// Adapter - the real process environment (`std::env`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessEnv;
+
+impl Environment for ProcessEnv {
+    fn var(&self, key: &str) -> Option<String> {
+        std::env::var(key).ok()
+    }
+}
+
+/// Adapter - in-memory environment, isolated from the process
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapEnv {
+    vars: HashMap<String, String>,
+}
+
+impl MapEnv {
+    /// Create an empty environment
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture the current process environment as a snapshot
+    pub fn from_process() -> Self {
+        std::env::vars().collect()
+    }
+
+    /// Builder-style setter
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    /// Set a variable
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.vars.insert(key.into(), value.into());
+    }
+
+    /// Remove a variable, returning its previous value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.vars.remove(key)
+    }
+}
+
+impl Environment for MapEnv {
+    fn var(&self, key: &str) -> Option<String> {
+        self.vars.get(key).cloned()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MapEnv {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            vars: iter
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+/// Adapter - several environments queried in priority order
+///
+/// The first layer that has a variable wins. Used to put `.env` files
+/// underneath the real process environment.
+#[derive(Debug, Clone)]
+pub struct LayeredEnv {
+    layers: Vec<Arc<dyn Environment>>,
+}
+
+impl LayeredEnv {
+    /// Start with the highest-priority layer
+    pub fn new(top: Arc<dyn Environment>) -> Self {
+        Self { layers: vec![top] }
+    }
+
+    /// Add a layer consulted only when all previous layers miss
+    pub fn with_fallback(mut self, env: Arc<dyn Environment>) -> Self {
+        self.layers.push(env);
+        self
+    }
+}
+
+impl Environment for LayeredEnv {
+    fn var(&self, key: &str) -> Option<String> {
+        self.layers.iter().find_map(|layer| layer.var(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_env_lookup() {
+        let mut env = MapEnv::new().with("TYL_POSTGRES_HOST", "db");
+        assert_eq!(env.var("TYL_POSTGRES_HOST").as_deref(), Some("db"));
+        assert_eq!(env.var("PGHOST"), None);
+
+        assert_eq!(env.remove("TYL_POSTGRES_HOST").as_deref(), Some("db"));
+        assert_eq!(env.var("TYL_POSTGRES_HOST"), None);
+    }
+
+    #[test]
+    fn test_map_env_from_iter() {
+        let env: MapEnv = [("A", "1"), ("B", "2")].into_iter().collect();
+        assert_eq!(env.var("A").as_deref(), Some("1"));
+        assert_eq!(env.var("B").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_layered_env_priority() {
+        let top = MapEnv::new().with("REDIS_HOST", "real");
+        let fallback = MapEnv::new()
+            .with("REDIS_HOST", "dotenv")
+            .with("REDIS_PORT", "6380");
+
+        let env = LayeredEnv::new(Arc::new(top)).with_fallback(Arc::new(fallback));
+
+        assert_eq!(env.var("REDIS_HOST").as_deref(), Some("real"));
+        assert_eq!(env.var("REDIS_PORT").as_deref(), Some("6380"));
+        assert_eq!(env.var("REDIS_PASSWORD"), None);
+    }
+
+    #[test]
+    fn test_process_env_reads_std_env() {
+        // PATH is set in any sane test environment; compare against std::env directly
+        assert_eq!(ProcessEnv.var("PATH"), std::env::var("PATH").ok());
+    }
+}
@@ -0,0 +1,116 @@
+//! Error aggregation
+//!
+//! Loading keeps going after a bad env var or YAML section so that every
+//! problem can be reported at once. [`ConfigErrors`] collects those failures,
+//! each tagged with the source it came from (usually the plugin name).
+
+use std::fmt;
+use tyl_errors::TylError;
+
+/// Every error collected while resolving configuration
+#[derive(Debug, Default)]
+pub struct ConfigErrors {
+    errors: Vec<(String, TylError)>,
+}
+
+impl ConfigErrors {
+    /// Create an empty collection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an error raised by `source`
+    pub fn push(&mut self, source: impl Into<String>, error: TylError) {
+        self.errors.push((source.into(), error));
+    }
+
+    /// Move all errors from `other` into this collection
+    pub fn extend(&mut self, other: ConfigErrors) {
+        self.errors.extend(other.errors);
+    }
+
+    /// Whether no error was recorded
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Number of recorded errors
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Iterate over `(source, error)` pairs in the order they were recorded
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TylError)> {
+        self.errors
+            .iter()
+            .map(|(source, error)| (source.as_str(), error))
+    }
+
+    /// `Ok(())` when empty, otherwise `Err(self)`
+    pub fn into_result(self) -> Result<(), ConfigErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} configuration error(s)", self.errors.len())?;
+        for (source, error) in &self.errors {
+            write!(f, "\n  [{source}] {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl From<ConfigErrors> for TylError {
+    fn from(mut errors: ConfigErrors) -> Self {
+        // A single error keeps its original kind (validation, configuration, ...)
+        if errors.len() == 1 {
+            if let Some((_, error)) = errors.errors.pop() {
+                return error;
+            }
+        }
+        TylError::configuration(errors.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collects_errors_in_order() {
+        let mut errors = ConfigErrors::new();
+        assert!(errors.is_empty());
+
+        errors.push(
+            "postgres",
+            TylError::configuration("Invalid TYL_POSTGRES_PORT"),
+        );
+        errors.push("redis", TylError::validation("host", "cannot be empty"));
+
+        assert_eq!(errors.len(), 2);
+        let sources: Vec<&str> = errors.iter().map(|(source, _)| source).collect();
+        assert_eq!(sources, vec!["postgres", "redis"]);
+
+        let message = errors.to_string();
+        assert!(message.contains("[postgres]"));
+        assert!(message.contains("TYL_POSTGRES_PORT"));
+        assert!(message.contains("[redis]"));
+    }
+
+    #[test]
+    fn test_single_error_keeps_its_kind() {
+        let mut errors = ConfigErrors::new();
+        errors.push("redis", TylError::validation("host", "cannot be empty"));
+
+        let error: TylError = errors.into();
+        assert!(matches!(error, TylError::Validation { .. }));
+    }
+}
@@ -0,0 +1,204 @@
+//! Configuration fingerprints
+//!
+//! `ConfigManager::fingerprint()` is a SHA-256 digest of the resolved values,
+//! rendered as JSON with sorted keys and sections, so it does not depend on
+//! field, map or plugin registration order. Log it at startup, expose it in
+//! health checks and compare it across replicas to spot drift.
+//!
+//! Secret fields (see `FieldMetadata::secret`) enter the digest as an
+//! HMAC-SHA256 keyed with the fingerprint key, so a weak password cannot be
+//! brute-forced from a logged fingerprint. The key is, in order:
+//!
+//! 1. `ConfigManagerBuilder::with_fingerprint_key`
+//! 2. the [`KEY_ENV_VAR`] env var of the builder's environment
+//!
+//! Without a key, secret fields are left out of the digest: replicas still
+//! agree, but a changed password does not change the fingerprint. Set the
+//! same key everywhere fingerprints are compared to cover secrets too.
+
+use crate::{ConfigResult, Environment};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tyl_errors::TylError;
+
+/// Env var holding the fingerprint key
+pub const KEY_ENV_VAR: &str = "TYL_CONFIG_FINGERPRINT_KEY";
+
+/// Key for the secret digests; `Debug` does not show it
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Key(Arc<[u8]>);
+
+impl Key {
+    pub(crate) fn new(bytes: impl AsRef<[u8]>) -> Self {
+        Self(Arc::from(bytes.as_ref()))
+    }
+
+    /// The explicit key, else the env var
+    pub(crate) fn resolve(explicit: Option<Key>, env: &dyn Environment) -> Option<Self> {
+        explicit.or_else(|| env.var(KEY_ENV_VAR).map(Key::new))
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// Hex SHA-256 of `sections` (section name, values)
+///
+/// `is_secret(section, field)` selects the top-level fields digested with
+/// `key`, or left out without one.
+pub(crate) fn compute(
+    sections: &[(&str, serde_yaml::Value)],
+    is_secret: impl Fn(&str, &str) -> bool,
+    key: Option<&Key>,
+) -> ConfigResult<String> {
+    let mut sections: Vec<&(&str, serde_yaml::Value)> = sections.iter().collect();
+    sections.sort_by_key(|(name, _)| *name);
+
+    let mut hasher = Sha256::new();
+    for (name, values) in sections {
+        let mut values = serde_json::to_value(values).map_err(|e| {
+            TylError::serialization(format!("Failed to serialize {name} config: {e}"))
+        })?;
+        if let Value::Object(fields) = &mut values {
+            match key {
+                Some(key) => {
+                    for (field, value) in fields.iter_mut() {
+                        if is_secret(name, field) {
+                            *value = Value::String(keyed_digest(key, name, field, value));
+                        }
+                    }
+                }
+                None => fields.retain(|field, _| !is_secret(name, field)),
+            }
+        }
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(canonical(&values).as_bytes());
+        hasher.update([0]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn keyed_digest(key: &Key, section: &str, field: &str, value: &Value) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key.0).expect("HMAC takes keys of any size");
+    mac.update(format!("{section}.{field}=").as_bytes());
+    mac.update(canonical(value).as_bytes());
+    format!("hmac-sha256:{}", hex(&mac.finalize().into_bytes()))
+}
+
+/// JSON text with object keys sorted at every level
+fn canonical(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", Value::from(key.as_str()), canonical(&map[key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MapEnv;
+
+    fn yaml(text: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    fn password(section: &str, field: &str) -> bool {
+        section == "db" && field == "password"
+    }
+
+    #[test]
+    fn test_independent_of_order() {
+        let key = Key::new("k");
+        let a = [
+            ("db", yaml("host: a\nopts:\n  x: 1\n  y: 2\npassword: p\n")),
+            ("cache", yaml("port: 1\n")),
+        ];
+        let b = [
+            ("cache", yaml("port: 1\n")),
+            ("db", yaml("password: p\nopts:\n  y: 2\n  x: 1\nhost: a\n")),
+        ];
+
+        let fingerprint = compute(&a, password, Some(&key)).unwrap();
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, compute(&b, password, Some(&key)).unwrap());
+        let changed = [("db", yaml("host: b\nopts:\n  x: 1\n  y: 2\npassword: p\n"))];
+        assert_ne!(
+            fingerprint,
+            compute(&changed, password, Some(&key)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_secrets_depend_on_key() {
+        let config = [("db", yaml("host: a\npassword: p\n"))];
+        let other_password = [("db", yaml("host: a\npassword: q\n"))];
+        let (k1, k2) = (Key::new("k1"), Key::new("k2"));
+
+        assert_ne!(
+            compute(&config, password, Some(&k1)).unwrap(),
+            compute(&config, password, Some(&k2)).unwrap()
+        );
+        assert_ne!(
+            compute(&config, password, Some(&k1)).unwrap(),
+            compute(&other_password, password, Some(&k1)).unwrap()
+        );
+        // Without secrets the key does not matter
+        let public = [("db", yaml("host: a\n"))];
+        assert_eq!(
+            compute(&public, password, Some(&k1)).unwrap(),
+            compute(&public, password, Some(&k2)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_secrets_left_out_without_key() {
+        let config = [("db", yaml("host: a\npassword: p\n"))];
+        let other_password = [("db", yaml("host: a\npassword: q\n"))];
+        let public = [("db", yaml("host: a\n"))];
+
+        let fingerprint = compute(&config, password, None).unwrap();
+        assert_eq!(
+            fingerprint,
+            compute(&other_password, password, None).unwrap()
+        );
+        assert_eq!(fingerprint, compute(&public, password, None).unwrap());
+        assert_ne!(
+            fingerprint,
+            compute(&config, password, Some(&Key::new("k"))).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_resolution() {
+        let env = MapEnv::new().with(KEY_ENV_VAR, "from-env");
+        assert_eq!(
+            Key::resolve(Some(Key::new("explicit")), &env),
+            Some(Key::new("explicit"))
+        );
+        assert_eq!(Key::resolve(None, &env), Some(Key::new("from-env")));
+        assert_eq!(Key::resolve(None, &MapEnv::new()), None);
+        assert_eq!(format!("{:?}", Key::new("s3cret")), "Key(..)");
+    }
+}
@@ -0,0 +1,116 @@
+//! Process-wide configuration
+//!
+//! Opt-in alternative to passing `ConfigManager` through every constructor:
+//! the binary installs the configuration once with [`init`], and any crate
+//! reads it with [`global`].
+//!
+//! ```rust
+//! use tyl_config::{ConfigManager, MapEnv, RedisConfig};
+//!
+//! tyl_config::init(
+//!     ConfigManager::builder()
+//!         .with_environment(MapEnv::new())
+//!         .with_redis(RedisConfig::default()),
+//! )
+//! .unwrap();
+//!
+//! let config = tyl_config::global().unwrap();
+//! assert_eq!(config.redis().unwrap().port, 6379);
+//! ```
+//!
+//! [`reload`] swaps in a new validated version for hot reload. Readers hold
+//! an `Arc` snapshot: a version they already loaded stays unchanged, the next
+//! call to [`global`] returns the new one. The configuration is kept in a
+//! [`SharedConfig`], so loading a snapshot takes no lock; [`shared`] returns
+//! that handle for [`Live`](crate::Live) views.
+
+use crate::{ConfigManager, ConfigManagerBuilder, ConfigResult, SharedConfig};
+use std::sync::{Arc, OnceLock};
+use tyl_errors::TylError;
+
+static GLOBAL: OnceLock<SharedConfig> = OnceLock::new();
+
+/// Build, validate and install the process-wide configuration
+///
+/// Fails if the configuration does not load or validate, and if it was
+/// already initialized (use [`reload`] to replace it).
+pub fn init(builder: ConfigManagerBuilder) -> ConfigResult<Arc<ConfigManager>> {
+    let shared = SharedConfig::from_builder(builder)?;
+    let config = shared.load();
+    GLOBAL
+        .set(shared)
+        .map_err(|_| TylError::configuration("Global configuration is already initialized"))?;
+    Ok(config)
+}
+
+/// The current process-wide configuration
+///
+/// Fails if [`init`] has not been called yet.
+pub fn global() -> ConfigResult<Arc<ConfigManager>> {
+    Ok(shared()?.load())
+}
+
+/// Build and validate a new version and swap it in, returning the old one
+///
+/// On error the current version stays installed (see [`SharedConfig::store`]).
+pub fn reload(builder: ConfigManagerBuilder) -> ConfigResult<Arc<ConfigManager>> {
+    shared()?.reload(builder)
+}
+
+/// Whether [`init`] has been called
+pub fn is_initialized() -> bool {
+    GLOBAL.get().is_some()
+}
+
+/// The handle behind [`global`]
+///
+/// Fails if [`init`] has not been called yet.
+pub fn shared() -> ConfigResult<&'static SharedConfig> {
+    GLOBAL.get().ok_or_else(|| {
+        TylError::configuration(
+            "Global configuration is not initialized, call tyl_config::init first",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapEnv, RedisConfig};
+
+    fn redis(port: &str) -> ConfigManagerBuilder {
+        ConfigManager::builder()
+            .with_environment(MapEnv::new().with("TYL_REDIS_PORT", port))
+            .with_redis(RedisConfig::default())
+    }
+
+    // The only test touching the process-wide state, so the steps run in order
+    #[test]
+    fn test_init_global_reload() {
+        assert!(!is_initialized());
+        assert!(global().is_err());
+        assert!(reload(redis("6380")).is_err());
+
+        let invalid = ConfigManager::builder()
+            .with_environment(MapEnv::new().with("TYL_REDIS_POOL_SIZE", "0"))
+            .with_redis(RedisConfig::default());
+        assert!(init(invalid).is_err());
+        assert!(!is_initialized());
+
+        init(redis("6380")).unwrap();
+        let snapshot = global().unwrap();
+        assert_eq!(snapshot.redis().unwrap().port, 6380);
+        assert!(init(redis("6381")).is_err());
+
+        let previous = reload(redis("6381")).unwrap();
+        assert!(Arc::ptr_eq(&previous, &snapshot));
+        assert_eq!(snapshot.redis().unwrap().port, 6380);
+        assert_eq!(global().unwrap().redis().unwrap().port, 6381);
+
+        let live = shared().unwrap().live::<RedisConfig>().unwrap();
+        assert_eq!(live.get().port, 6381);
+
+        assert!(reload(redis("not-a-port")).is_err());
+        assert_eq!(global().unwrap().redis().unwrap().port, 6381);
+    }
+}
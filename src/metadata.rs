@@ -0,0 +1,242 @@
+//! Per-field plugin metadata
+//!
+//! Plugins describe their fields through `ConfigPlugin::field_metadata`. The
+//! template generator writes this as a comment above every key:
+//!
+//! ```yaml
+//! postgres:
+//!   # Connection pool timeout
+//!   # Default: 30
+//!   # Unit: seconds
+//!   # Env: TYL_POSTGRES_TIMEOUT_SECONDS
+//!   timeout_seconds: 30
+//! ```
+//!
+//! Declaring metadata is optional. Every serialized field gets an entry
+//! anyway (see [`resolve`]): the default comes from the plugin's `Default`,
+//! the env var is inferred as `{ENV_PREFIX}_{FIELD}`, and fields named like
+//! credentials are marked secret.
+
+use crate::rules::Rule;
+use serde_yaml::Value;
+
+/// Field name fragments that mark an undeclared field as secret
+const SECRET_HINTS: &[&str] = &["password", "secret", "token", "api_key", "private_key"];
+
+/// Documentation for one plugin field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMetadata {
+    /// YAML key of the field
+    pub name: String,
+    pub description: String,
+    /// Default value as written in YAML, filled in from `Default` if unset
+    pub default: Option<String>,
+    /// Accepted env vars, highest priority first
+    pub env_vars: Vec<String>,
+    /// JSON Schema type name (`string`, `integer`, ...), inferred from the
+    /// default if unset
+    pub value_type: Option<String>,
+    /// Unit of a numeric value (`seconds`, `connections`)
+    pub unit: Option<String>,
+    /// Whether the value is a credential that should not be shown
+    pub secret: bool,
+    /// Old YAML keys still accepted for this field
+    pub deprecated_keys: Vec<String>,
+    /// Old env vars still accepted, below `env_vars`
+    pub deprecated_env: Vec<String>,
+    /// Validation rules, checked by `rules::validate` and added to the JSON
+    /// Schema
+    pub rules: Vec<Rule>,
+}
+
+impl FieldMetadata {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the accepted env vars, highest priority first
+    pub fn env<I, S>(mut self, env_vars: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env_vars = env_vars.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn value_type(mut self, value_type: impl Into<String>) -> Self {
+        self.value_type = Some(value_type.into());
+        self
+    }
+
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    pub fn default_value(mut self, default: impl Into<String>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
+    pub fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    /// Add a validation rule (see [`rules`](crate::rules))
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Also accept the old YAML key `key`, with a deprecation warning
+    pub fn deprecated_key(mut self, key: impl Into<String>) -> Self {
+        self.deprecated_keys.push(key.into());
+        self
+    }
+
+    /// Also accept old env vars, after `env_vars`, with a deprecation warning
+    pub fn deprecated_env<I, S>(mut self, env_vars: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deprecated_env
+            .extend(env_vars.into_iter().map(Into::into));
+        self
+    }
+
+    /// Comment lines for a config template, without the leading `#`
+    pub fn comment_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.description.is_empty() {
+            lines.push(self.description.clone());
+        }
+        if let Some(default) = &self.default {
+            let default = if self.secret { "(secret)" } else { default };
+            lines.push(format!("Default: {default}"));
+        }
+        if let Some(unit) = &self.unit {
+            lines.push(format!("Unit: {unit}"));
+        }
+        if !self.env_vars.is_empty() {
+            lines.push(format!("Env: {}", self.env_vars.join(", ")));
+        }
+        lines
+    }
+}
+
+/// Complete the declared metadata with one entry per field of `defaults`
+///
+/// Fields come in the order of `defaults` (the serialized `Default`), then
+/// declared fields that are not serialized by default (such as unset
+/// `Option`s).
+pub fn resolve(
+    declared: Vec<FieldMetadata>,
+    defaults: &Value,
+    env_prefix: &str,
+) -> Vec<FieldMetadata> {
+    let mut declared = declared;
+    let mut resolved = Vec::new();
+
+    if let Value::Mapping(fields) = defaults {
+        for (key, default) in fields {
+            let Some(name) = key.as_str() else {
+                continue;
+            };
+            let mut field = match declared.iter().position(|field| field.name == name) {
+                Some(index) => declared.remove(index),
+                None => infer(name, env_prefix),
+            };
+            if field.default.is_none() {
+                field.default = render_default(default);
+            }
+            if field.value_type.is_none() {
+                field.value_type = type_name(default).map(str::to_string);
+            }
+            resolved.push(field);
+        }
+    }
+
+    resolved.extend(declared);
+    resolved
+}
+
+fn infer(name: &str, env_prefix: &str) -> FieldMetadata {
+    let mut field = FieldMetadata::new(name, "");
+    if !env_prefix.is_empty() {
+        field.env_vars = vec![format!("{env_prefix}_{}", name.to_uppercase())];
+    }
+    field.secret = SECRET_HINTS.iter().any(|hint| name.contains(hint));
+    field
+}
+
+/// Type name used by the JSON Schema of the same value
+fn type_name(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some("boolean"),
+        Value::Number(number) if number.is_f64() => Some("number"),
+        Value::Number(_) => Some("integer"),
+        Value::String(_) => Some("string"),
+        Value::Sequence(_) => Some("array"),
+        Value::Mapping(_) => Some("object"),
+        Value::Tagged(tagged) => type_name(&tagged.value),
+    }
+}
+
+fn render_default(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) => serde_yaml::to_string(value)
+            .ok()
+            .map(|s| s.trim_end().to_string()),
+        // Nested values are shown by the template itself
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_infers_undeclared_fields() {
+        let defaults: Value =
+            serde_yaml::from_str("api_key: dev-key\ntimeout_ms: 500\nregion: null\n").unwrap();
+        let declared = vec![FieldMetadata::new("timeout_ms", "Request timeout").unit("ms")];
+
+        let fields = resolve(declared, &defaults, "MY_SERVICE");
+
+        assert_eq!(fields[0].name, "api_key");
+        assert_eq!(fields[0].env_vars, vec!["MY_SERVICE_API_KEY"]);
+        assert!(fields[0].secret);
+        assert_eq!(fields[1].description, "Request timeout");
+        assert_eq!(fields[1].default.as_deref(), Some("500"));
+        assert_eq!(fields[1].value_type.as_deref(), Some("integer"));
+        assert_eq!(fields[2].default, None);
+    }
+
+    #[test]
+    fn test_comment_lines_hide_secret_defaults() {
+        let field = FieldMetadata::new("password", "Database password")
+            .default_value("password")
+            .env(["TYL_POSTGRES_PASSWORD", "PGPASSWORD"])
+            .secret();
+
+        assert_eq!(
+            field.comment_lines(),
+            vec![
+                "Database password",
+                "Default: (secret)",
+                "Env: TYL_POSTGRES_PASSWORD, PGPASSWORD",
+            ]
+        );
+    }
+}
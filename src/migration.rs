@@ -0,0 +1,300 @@
+//! Versioned config files and migrations
+//!
+//! A config file may start with a `version:` key. Plugins declare upgrade
+//! steps in `ConfigPlugin::migrations`: a [`Migration`] with `from: 1`
+//! upgrades that plugin's section from version 1 to 2. The current version
+//! is one past the highest step of the registered plugins, and a file
+//! without `version:` is at version 1.
+//!
+//! ```rust
+//! use tyl_config::migration::Migration;
+//!
+//! // v1 -> v2: `timeout_seconds` was renamed to `timeout`
+//! let steps = vec![Migration::rename(1, "timeout_seconds", "timeout")];
+//! # assert_eq!(steps[0].to_string(), "rename `timeout_seconds` to `timeout`");
+//! ```
+//!
+//! Loading upgrades older files in memory, step by step, and logs a warning
+//! for each step applied. With `ConfigManagerBuilder::with_migration_rewrite`
+//! the upgraded file is also written back (atomically, at the current
+//! version). Rewriting re-serializes the YAML, so comments are not kept.
+//! A file is left alone, with a warning, when marking it current would skip
+//! steps on the next load: when migrated keys come from included files, or
+//! when it has sections no registered plugin knows.
+//!
+//! A file at a higher version than the registered plugins know is read
+//! without upgrades: the builder may not have all the plugins the file was
+//! written for (or may get them later), so that is not an error.
+
+use crate::ConfigResult;
+use serde_yaml::{Mapping, Value};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use tyl_errors::TylError;
+
+/// Top-level key holding the version of a config file
+pub const VERSION_KEY: &str = "version";
+
+/// Version of a file without a `version:` key
+pub const INITIAL_VERSION: u32 = 1;
+
+type MigrationFn = dyn Fn(&mut Mapping) -> ConfigResult<()> + Send + Sync;
+
+/// One upgrade step of a plugin section, from `from` to `from + 1`
+#[derive(Clone)]
+pub struct Migration {
+    from: u32,
+    description: String,
+    apply: Arc<MigrationFn>,
+}
+
+impl Migration {
+    /// A step that edits the plugin's section in place
+    pub fn new(
+        from: u32,
+        description: impl Into<String>,
+        apply: impl Fn(&mut Mapping) -> ConfigResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            from,
+            description: description.into(),
+            apply: Arc::new(apply),
+        }
+    }
+
+    /// Move the value of key `old` to `new`
+    ///
+    /// Fails if the section sets both.
+    pub fn rename(from: u32, old: &str, new: &str) -> Self {
+        let (old, new) = (old.to_string(), new.to_string());
+        let description = format!("rename `{old}` to `{new}`");
+        Self::new(from, description, move |section| {
+            let Some(value) = section.remove(old.as_str()) else {
+                return Ok(());
+            };
+            if section.contains_key(new.as_str()) {
+                return Err(TylError::configuration(format!(
+                    "Both `{old}` and its replacement `{new}` are set"
+                )));
+            }
+            section.insert(Value::from(new.as_str()), value);
+            Ok(())
+        })
+    }
+
+    /// The version this step upgrades from
+    pub fn from_version(&self) -> u32 {
+        self.from
+    }
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+impl fmt::Debug for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migration")
+            .field("from", &self.from)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// A step that was applied to a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub section: String,
+    pub from: u32,
+    pub description: String,
+}
+
+impl fmt::Display for AppliedMigration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "migrated `{}` from version {} to {}: {}",
+            self.section,
+            self.from,
+            self.from + 1,
+            self.description
+        )
+    }
+}
+
+/// One past the highest step of `plugins` (section name, steps)
+pub(crate) fn current_version(plugins: &[(&str, Vec<Migration>)]) -> u32 {
+    plugins
+        .iter()
+        .flat_map(|(_, steps)| steps.iter().map(|step| step.from + 1))
+        .max()
+        .unwrap_or(INITIAL_VERSION)
+}
+
+/// Upgrade `document` to the current version and remove its `version:` key
+///
+/// Returns the steps that changed a section.
+pub(crate) fn migrate(
+    document: &mut Value,
+    plugins: &[(&str, Vec<Migration>)],
+) -> ConfigResult<Vec<AppliedMigration>> {
+    let Value::Mapping(document) = document else {
+        return Ok(Vec::new());
+    };
+    let version = match document.remove(VERSION_KEY) {
+        None => INITIAL_VERSION,
+        Some(value) => value
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= INITIAL_VERSION)
+            .ok_or_else(|| TylError::validation(VERSION_KEY, "must be a positive integer"))?,
+    };
+    // Nothing to do for a version the registered plugins do not reach
+    let mut applied = Vec::new();
+    for from in version..current_version(plugins) {
+        for (section, steps) in plugins {
+            let Some(Value::Mapping(values)) = document.get_mut(*section) else {
+                continue;
+            };
+            for step in steps.iter().filter(|step| step.from == from) {
+                let before = values.clone();
+                (step.apply)(values).map_err(|e| {
+                    TylError::configuration(format!(
+                        "Migration of `{section}` from version {from} failed ({step}): {e}"
+                    ))
+                })?;
+                // Only report steps that changed something
+                if *values == before {
+                    continue;
+                }
+                applied.push(AppliedMigration {
+                    section: section.to_string(),
+                    from,
+                    description: step.description.clone(),
+                });
+            }
+        }
+    }
+    Ok(applied)
+}
+
+/// Upgrade the file at `path` itself, without touching included files, and
+/// write it back at the current version
+///
+/// `loaded` is the file as loading resolved and migrated it. Returns `false`
+/// without writing when reading the rewritten file would not give `loaded`
+/// again, or when `loaded` has sections without a registered plugin, whose
+/// steps would be skipped once the file is marked current.
+pub(crate) fn rewrite_file(
+    path: &Path,
+    plugins: &[(&str, Vec<Migration>)],
+    loaded: &Value,
+) -> ConfigResult<bool> {
+    let unknown_section = loaded.as_mapping().is_some_and(|sections| {
+        sections.keys().filter_map(Value::as_str).any(|section| {
+            section != crate::yaml::INCLUDE_KEY && !plugins.iter().any(|(name, _)| *name == section)
+        })
+    });
+    if unknown_section {
+        return Ok(false);
+    }
+
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    let mut document: Value = serde_yaml::from_str(&content).map_err(|e| {
+        TylError::configuration(format!("Failed to parse YAML {}: {e}", path.display()))
+    })?;
+    migrate(&mut document, plugins)?;
+
+    let mut versioned = Mapping::new();
+    versioned.insert(
+        Value::from(VERSION_KEY),
+        Value::from(current_version(plugins)),
+    );
+    if let Value::Mapping(rest) = document {
+        versioned.extend(rest);
+    }
+
+    // Keys from included files stay at their old version and would no
+    // longer be migrated
+    let mut reread = crate::yaml::resolve_includes_at(Value::Mapping(versioned.clone()), path)?;
+    migrate(&mut reread, plugins)?;
+    if reread != *loaded {
+        return Ok(false);
+    }
+
+    let yaml = serde_yaml::to_string(&versioned)
+        .map_err(|e| TylError::serialization(format!("Failed to serialize YAML: {e}")))?;
+    crate::template::write_atomic(path, &yaml)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugins() -> Vec<(&'static str, Vec<Migration>)> {
+        let split = Migration::new(2, "split `endpoint` into `host` and `port`", |section| {
+            if let Some(Value::String(endpoint)) = section.remove("endpoint") {
+                let (host, port) = endpoint.split_once(':').unwrap_or((&endpoint, "80"));
+                section.insert("host".into(), host.into());
+                section.insert("port".into(), port.parse::<u16>().unwrap_or(80).into());
+            }
+            Ok(())
+        });
+        vec![
+            (
+                "api",
+                vec![Migration::rename(1, "timeout_seconds", "timeout"), split],
+            ),
+            ("cache", vec![]),
+        ]
+    }
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_from_each_version() {
+        assert_eq!(current_version(&plugins()), 3);
+
+        let mut v1 = yaml("api:\n  timeout_seconds: 5\n  endpoint: example.com:8080\n");
+        let applied = migrate(&mut v1, &plugins()).unwrap();
+        assert_eq!(applied.len(), 2);
+        assert_eq!(
+            applied[0].to_string(),
+            "migrated `api` from version 1 to 2: rename `timeout_seconds` to `timeout`"
+        );
+        assert_eq!(
+            v1,
+            yaml("api:\n  timeout: 5\n  host: example.com\n  port: 8080\n")
+        );
+
+        let mut v2 = yaml("version: 2\napi:\n  timeout_seconds: 5\n");
+        let applied = migrate(&mut v2, &plugins()).unwrap();
+        assert!(applied.is_empty());
+        assert_eq!(v2, yaml("api:\n  timeout_seconds: 5\n"));
+    }
+
+    #[test]
+    fn test_migrate_invalid_and_newer_versions() {
+        // Written for plugins that are not registered: read as is
+        let mut newer = yaml("version: 4\napi:\n  timeout_seconds: 5\n");
+        assert!(migrate(&mut newer, &plugins()).unwrap().is_empty());
+        assert_eq!(newer, yaml("api:\n  timeout_seconds: 5\n"));
+
+        let mut invalid = yaml("version: two\n");
+        assert!(migrate(&mut invalid, &plugins()).is_err());
+
+        let mut both = yaml("api:\n  timeout_seconds: 5\n  timeout: 6\n");
+        assert!(migrate(&mut both, &plugins()).is_err());
+    }
+}
@@ -0,0 +1,417 @@
+//! Organization-wide policy files
+//!
+//! A policy is a YAML file of limits that every service's configuration has
+//! to meet, maintained once by a platform team and checked against any
+//! resolved `ConfigManager`:
+//!
+//! ```yaml
+//! rules:
+//!   - path: "*.pool_size"        # `*` matches every section with the key
+//!     max: 50
+//!     profiles: [production]     # only checked for these profiles
+//!     description: Shared PgBouncer has 500 slots
+//!   - path: redis.url            # Redis over TLS: there is no `tls`
+//!     url_scheme: [rediss]       # flag, the URL scheme decides
+//!     severity: warning          # default: error
+//!   - path: postgres.host
+//!     pattern: '\.internal$'
+//! ```
+//!
+//! A rule applies to the configured sections it names; a service without a
+//! `redis` section passes every `redis.*` rule. An explicit path that is not
+//! set (`null` or `""`) is a violation, a `*` path skips sections where it is
+//! not set or that have no such key. A path naming a key its section does not
+//! have fails the evaluation, so a misspelled rule is not mistaken for an
+//! unset value.
+//!
+//! Checks: `equals`, `one_of`, `min`, `max`, `pattern`, `non_empty` and
+//! `url_scheme`, with the same semantics as the field rules in
+//! [`rules`](crate::rules). Run `tyl-config policy POLICY.yaml CONFIG.yaml`
+//! to check a config file in CI.
+
+use crate::lint::Severity;
+use crate::profile::Profile;
+use crate::rules::{Rule, RuleKind};
+use crate::{registry, ConfigManager, ConfigResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use tyl_errors::TylError;
+
+/// Section wildcard in a rule path
+pub const ANY_SECTION: &str = "*";
+
+/// Limits for every service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Checks on one key path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// `section.key`, or `*.key` for every section
+    pub path: String,
+    /// Profiles the rule applies to, every profile when empty
+    pub profiles: Vec<Profile>,
+    pub severity: Severity,
+    /// Why the rule exists, shown with violations
+    pub description: Option<String>,
+    pub checks: Vec<Rule>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    path: String,
+    #[serde(default)]
+    profiles: Vec<String>,
+    severity: Option<Severity>,
+    description: Option<String>,
+    equals: Option<serde_json::Value>,
+    one_of: Option<Vec<serde_json::Value>>,
+    min: Option<i64>,
+    max: Option<i64>,
+    pattern: Option<String>,
+    #[serde(default)]
+    non_empty: bool,
+    url_scheme: Option<Vec<String>>,
+}
+
+impl RuleEntry {
+    fn into_rule(self) -> ConfigResult<PolicyRule> {
+        let (section, keys) = registry::split_path(&self.path)?;
+        if keys.is_empty() {
+            return Err(TylError::configuration(format!(
+                "`{section}` is a section, a rule needs a key path such as `{section}.host`"
+            )));
+        }
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|name| name.parse())
+            .collect::<ConfigResult<Vec<Profile>>>()?;
+
+        let mut checks = Vec::new();
+        if let Some(value) = self.equals {
+            let message = format!("must be {value}");
+            checks.push(Rule::one_of([value]).message(message));
+        }
+        if let Some(values) = self.one_of {
+            checks.push(Rule::one_of(values));
+        }
+        if self.min.is_some() || self.max.is_some() {
+            checks.push(Rule {
+                kind: RuleKind::Range {
+                    min: self.min,
+                    max: self.max,
+                },
+                message: None,
+            });
+        }
+        if let Some(pattern) = self.pattern {
+            regex::Regex::new(&pattern).map_err(|e| {
+                TylError::configuration(format!("Invalid pattern `{pattern}`: {e}"))
+            })?;
+            checks.push(Rule::pattern(pattern));
+        }
+        if self.non_empty {
+            checks.push(Rule::non_empty());
+        }
+        if let Some(schemes) = self.url_scheme {
+            checks.push(Rule::url_scheme(schemes));
+        }
+        if checks.is_empty() {
+            return Err(TylError::configuration(
+                "Rule has no check (equals, one_of, min, max, pattern, non_empty, url_scheme)",
+            ));
+        }
+
+        Ok(PolicyRule {
+            path: self.path,
+            profiles,
+            severity: self.severity.unwrap_or(Severity::Error),
+            description: self.description,
+            checks,
+        })
+    }
+}
+
+impl Policy {
+    pub fn from_yaml_str(content: &str) -> ConfigResult<Self> {
+        let file: PolicyFile = serde_yaml::from_str(content)
+            .map_err(|e| TylError::configuration(format!("Failed to parse policy: {e}")))?;
+        let rules = file
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let path = entry.path.clone();
+                entry.into_rule().map_err(|e| {
+                    TylError::configuration(format!("Policy rule {} (`{path}`): {e}", index + 1))
+                })
+            })
+            .collect::<ConfigResult<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> ConfigResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            TylError::configuration(format!("Failed to read policy {}: {e}", path.display()))
+        })?;
+        Self::from_yaml_str(&content)
+            .map_err(|e| TylError::configuration(format!("{}: {e}", path.display())))
+    }
+
+    /// Check `config` under its active profile
+    ///
+    /// Fails on an unknown `TYL_PROFILE` value or a rule naming a key its
+    /// section does not have.
+    pub fn evaluate(&self, config: &ConfigManager) -> ConfigResult<PolicyReport> {
+        let profile = config.profile()?;
+        let configured = config.section_names();
+        let mut violations = Vec::new();
+
+        for rule in &self.rules {
+            if !rule.profiles.is_empty() && !rule.profiles.contains(&profile) {
+                continue;
+            }
+            let (section, keys) = registry::split_path(&rule.path)?;
+            let wildcard = section == ANY_SECTION;
+            let sections = configured
+                .iter()
+                .filter(|name| wildcard || **name == section);
+
+            for name in sections {
+                if wildcard
+                    && !config
+                        .field_metadata(name)?
+                        .iter()
+                        .any(|field| field.name == keys[0])
+                {
+                    continue;
+                }
+                let path = format!("{name}.{}", keys.join("."));
+                let value = config
+                    .get_value(&path)
+                    .and_then(|value| {
+                        serde_json::to_value(value).map_err(|e| {
+                            TylError::serialization(format!("Failed to read `{path}`: {e}"))
+                        })
+                    })
+                    .map_err(|e| {
+                        TylError::configuration(format!("Policy rule `{}`: {e}", rule.path))
+                    })?;
+                if value.is_null() || value.as_str() == Some("") {
+                    if !wildcard {
+                        violations.push(Violation::new(rule, path, "is not set".to_string()));
+                    }
+                    continue;
+                }
+
+                let mut field = serde_json::Map::new();
+                field.insert(path.clone(), value);
+                for check in &rule.checks {
+                    if let Err(message) = check.check(&path, &field) {
+                        violations.push(Violation::new(rule, path.clone(), message));
+                    }
+                }
+            }
+        }
+
+        violations.sort_by_key(|violation| std::cmp::Reverse(violation.severity));
+        Ok(PolicyReport {
+            profile,
+            violations,
+        })
+    }
+}
+
+/// A value that breaks a policy rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    /// Resolved key path (`postgres.pool_size`)
+    pub path: String,
+    /// Path of the rule, possibly with `*`
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Violation {
+    fn new(rule: &PolicyRule, path: String, message: String) -> Self {
+        Self {
+            path,
+            rule: rule.path.clone(),
+            severity: rule.severity,
+            message,
+            description: rule.description.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.severity, self.path, self.message)?;
+        if let Some(description) = &self.description {
+            write!(f, " ({description})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every violation for one configuration, most severe first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyReport {
+    pub profile: Profile,
+    pub violations: Vec<Violation>,
+}
+
+impl PolicyReport {
+    /// Whether any violated rule has error severity
+    pub fn has_errors(&self) -> bool {
+        self.violations
+            .iter()
+            .any(|violation| violation.severity == Severity::Error)
+    }
+
+    /// One line per violation
+    pub fn to_text(&self) -> String {
+        if self.violations.is_empty() {
+            return "No violations\n".to_string();
+        }
+        self.violations
+            .iter()
+            .map(|violation| format!("{violation}\n"))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> ConfigResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| TylError::serialization(format!("Failed to serialize policy report: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapEnv, PostgresConfig, RedisConfig};
+
+    const POLICY: &str = r#"
+rules:
+  - path: "*.pool_size"
+    max: 8
+    profiles: [prod]
+    description: shared pooler
+  - path: redis.url
+    url_scheme: [rediss]
+    severity: warning
+  - path: postgres.port
+    equals: 5432
+"#;
+
+    fn config(profile: Profile) -> ConfigManager {
+        ConfigManager::builder()
+            .with_environment(MapEnv::new())
+            .with_profile(profile)
+            .with_postgres(PostgresConfig {
+                pool_size: 20,
+                port: 6432,
+                ..PostgresConfig::default()
+            })
+            .with_redis(RedisConfig::default())
+            .build()
+    }
+
+    #[test]
+    fn test_evaluate_by_profile() {
+        let policy = Policy::from_yaml_str(POLICY).unwrap();
+
+        let report = policy.evaluate(&config(Profile::Production)).unwrap();
+        let lines: Vec<String> = report.violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "error: postgres.pool_size must be at most 8 (shared pooler)",
+                "error: postgres.port must be 5432",
+                "warning: redis.url is not set",
+            ]
+        );
+        assert!(report.has_errors());
+
+        // The pool limit only applies in production
+        let report = policy.evaluate(&config(Profile::Staging)).unwrap();
+        assert_eq!(report.violations.len(), 2);
+    }
+
+    #[test]
+    fn test_redis_tls_through_url_scheme() {
+        let policy =
+            Policy::from_yaml_str("rules:\n  - path: redis.url\n    url_scheme: [rediss]\n")
+                .unwrap();
+        let redis = |url: &str| {
+            ConfigManager::builder()
+                .with_environment(MapEnv::new().with("TYL_REDIS_URL", url))
+                .with_redis(RedisConfig::default())
+                .build()
+        };
+
+        let report = policy.evaluate(&redis("rediss://cache:6380")).unwrap();
+        assert!(report.violations.is_empty());
+        let report = policy.evaluate(&redis("redis://cache:6379")).unwrap();
+        assert_eq!(report.violations.len(), 1);
+        assert!(report.has_errors());
+
+        // There is no `redis.tls` key to require
+        let policy =
+            Policy::from_yaml_str("rules:\n  - path: redis.tls\n    equals: true\n").unwrap();
+        assert!(policy.evaluate(&redis("rediss://cache:6380")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let policy =
+            Policy::from_yaml_str("rules:\n  - path: postgres.pool_sise\n    max: 8\n").unwrap();
+        let message = policy
+            .evaluate(&config(Profile::Development))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("postgres.pool_sise"), "{message}");
+
+        // A wildcard only looks at sections that have the key
+        let policy =
+            Policy::from_yaml_str("rules:\n  - path: \"*.username\"\n    equals: svc\n").unwrap();
+        let report = policy.evaluate(&config(Profile::Development)).unwrap();
+        let paths: Vec<&str> = report.violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["postgres.username"]);
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        for (policy, error) in [
+            ("rules:\n  - path: redis\n    non_empty: true\n", "key path"),
+            ("rules:\n  - path: redis.host\n", "no check"),
+            (
+                "rules:\n  - path: redis.host\n    pattern: '('\n",
+                "Invalid pattern",
+            ),
+            (
+                "rules:\n  - path: a.b\n    max: 1\n    profiles: [qa]\n",
+                "Unknown profile",
+            ),
+            ("rules:\n  - path: a.b\n    maximum: 1\n", "unknown field"),
+        ] {
+            let message = Policy::from_yaml_str(policy).unwrap_err().to_string();
+            assert!(message.contains(error), "{message}");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Deployment profiles
+//!
+//! The active [`Profile`] says what kind of deployment a configuration is
+//! for. It decides how severe lint findings are (see [`lint`](crate::lint)).
+//! It comes from `ConfigManagerBuilder::with_profile`, else the
+//! [`PROFILE_ENV_VAR`] env var of the builder's environment, else
+//! `development`. The env var is only parsed when the profile is needed, so
+//! a bad value fails `lint()` and policy checks, not loading.
+
+use crate::{ConfigResult, Environment};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use tyl_errors::TylError;
+
+/// Env var naming the active profile
+pub const PROFILE_ENV_VAR: &str = "TYL_PROFILE";
+
+/// Kind of deployment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    #[default]
+    Development,
+    Test,
+    Staging,
+    Production,
+}
+
+impl Profile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Profile::Development => "development",
+            Profile::Test => "test",
+            Profile::Staging => "staging",
+            Profile::Production => "production",
+        }
+    }
+
+    /// The profile named by [`PROFILE_ENV_VAR`], `development` when unset
+    pub fn from_env(env: &dyn Environment) -> ConfigResult<Self> {
+        Self::from_var(env.var(PROFILE_ENV_VAR).as_deref())
+    }
+
+    /// The profile named by a [`PROFILE_ENV_VAR`] value, `development` when unset
+    pub(crate) fn from_var(value: Option<&str>) -> ConfigResult<Self> {
+        match value {
+            Some(name) => name
+                .parse()
+                .map_err(|e: TylError| TylError::validation(PROFILE_ENV_VAR, e.to_string())),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = TylError;
+
+    /// Full names and the usual short forms (`dev`, `prod`), any case
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Profile::Development),
+            "test" => Ok(Profile::Test),
+            "staging" | "stage" => Ok(Profile::Staging),
+            "production" | "prod" => Ok(Profile::Production),
+            _ => Err(TylError::configuration(format!(
+                "Unknown profile `{name}`, expected development, test, staging or production"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MapEnv;
+
+    #[test]
+    fn test_profile_from_env() {
+        assert_eq!(
+            Profile::from_env(&MapEnv::new()).unwrap(),
+            Profile::Development
+        );
+        let env = MapEnv::new().with(PROFILE_ENV_VAR, "Prod");
+        assert_eq!(Profile::from_env(&env).unwrap(), Profile::Production);
+        assert_eq!("stage".parse::<Profile>().unwrap(), Profile::Staging);
+
+        let env = MapEnv::new().with(PROFILE_ENV_VAR, "qa");
+        let error = Profile::from_env(&env).unwrap_err().to_string();
+        assert!(error.contains(PROFILE_ENV_VAR), "{error}");
+    }
+}
@@ -0,0 +1,279 @@
+//! Type-erased plugin access
+//!
+//! Custom plugins registered with `ConfigManagerBuilder::with_plugin` are
+//! stored next to the built-in ones behind [`ErasedPlugin`], which exposes
+//! every plugin as a `serde_yaml::Value` tree. That is what lets
+//! `ConfigManager::get_value` / `set` address any plugin by a dotted path
+//! (`redis.database`, `my_service.timeout_ms`) without knowing its type.
+
+use crate::migration::Migration;
+use crate::{metadata, ConfigPlugin, ConfigResult, Environment, FieldMetadata};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+use std::any::Any;
+use tyl_errors::TylError;
+
+/// A plugin seen through its serialized form
+///
+/// Implemented for every `ConfigPlugin` that is also serde-compatible. Method
+/// names differ from `ConfigPlugin` so both traits can be in scope.
+pub(crate) trait ErasedPlugin: std::fmt::Debug + Send + Sync {
+    /// Section name, same as `ConfigPlugin::name`
+    fn section_name(&self) -> &'static str;
+
+    fn validate_section(&self) -> ConfigResult<()>;
+
+    fn to_value(&self) -> ConfigResult<Value>;
+
+    /// The plugin's `Default`, serialized
+    fn default_value(&self) -> ConfigResult<Value>;
+
+    /// Declared and inferred metadata, one entry per field
+    fn fields(&self) -> ConfigResult<Vec<FieldMetadata>>;
+
+    /// Replace all fields with `value`, without validating
+    fn replace_from(&mut self, value: Value) -> ConfigResult<()>;
+
+    fn apply_env(&mut self, env: &dyn Environment) -> ConfigResult<()>;
+
+    /// Same as `ConfigPlugin::dependencies`
+    fn section_dependencies(&self) -> Vec<&'static str>;
+
+    /// Same as `ConfigPlugin::migrations`
+    fn section_migrations(&self) -> Vec<Migration>;
+
+    fn clone_box(&self) -> Box<dyn ErasedPlugin>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> ErasedPlugin for T
+where
+    T: ConfigPlugin + Default + Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn section_name(&self) -> &'static str {
+        self.name()
+    }
+
+    fn validate_section(&self) -> ConfigResult<()> {
+        self.validate()
+    }
+
+    fn to_value(&self) -> ConfigResult<Value> {
+        serde_yaml::to_value(self).map_err(|e| {
+            TylError::serialization(format!("Failed to serialize {} config: {e}", self.name()))
+        })
+    }
+
+    fn default_value(&self) -> ConfigResult<Value> {
+        T::default().to_value()
+    }
+
+    fn fields(&self) -> ConfigResult<Vec<FieldMetadata>> {
+        Ok(metadata::resolve(
+            self.field_metadata(),
+            &self.default_value()?,
+            self.env_prefix(),
+        ))
+    }
+
+    fn replace_from(&mut self, value: Value) -> ConfigResult<()> {
+        *self = serde_yaml::from_value(value).map_err(|e| {
+            TylError::configuration(format!("Failed to parse {} config: {e}", self.name()))
+        })?;
+        Ok(())
+    }
+
+    fn apply_env(&mut self, env: &dyn Environment) -> ConfigResult<()> {
+        self.merge_env_from(env)
+    }
+
+    fn section_dependencies(&self) -> Vec<&'static str> {
+        self.dependencies()
+    }
+
+    fn section_migrations(&self) -> Vec<Migration> {
+        self.migrations()
+    }
+
+    fn clone_box(&self) -> Box<dyn ErasedPlugin> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Clone for Box<dyn ErasedPlugin> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Split `section.key.path` into the section and the keys below it
+pub(crate) fn split_path(path: &str) -> ConfigResult<(&str, Vec<&str>)> {
+    let mut segments = path.split('.');
+    let section = segments.next().unwrap_or_default();
+    let keys: Vec<&str> = segments.collect();
+    if section.is_empty() || keys.iter().any(|key| key.is_empty()) {
+        return Err(TylError::configuration(format!(
+            "Invalid configuration path `{path}`"
+        )));
+    }
+    Ok((section, keys))
+}
+
+/// Error for a section no plugin is registered under
+pub(crate) fn unknown_section(section: &str) -> TylError {
+    TylError::configuration(format!("Unknown configuration section `{section}`"))
+}
+
+/// Add `key: null` to the section `tree` when `key` is a declared field it
+/// lacks
+///
+/// Unset `Option` fields are not serialized; this lets them be addressed by
+/// path like the others.
+pub(crate) fn declare_field(tree: &mut Value, key: &str, fields: &[FieldMetadata]) {
+    let Value::Mapping(map) = tree else {
+        return;
+    };
+    if !map.contains_key(key) && fields.iter().any(|field| field.name == key) {
+        map.insert(Value::from(key), Value::Null);
+    }
+}
+
+/// Follow `keys` into `tree`, failing with the full `path` when a key is missing
+pub(crate) fn value_at_mut<'a>(
+    tree: &'a mut Value,
+    keys: &[&str],
+    path: &str,
+) -> ConfigResult<&'a mut Value> {
+    keys.iter().try_fold(tree, |node, key| {
+        node.get_mut(*key)
+            .ok_or_else(|| TylError::validation(path, "unknown configuration key"))
+    })
+}
+
+/// Section names of `plugins`, each after the sections it depends on
+///
+/// Keeps the given order where dependencies allow it.
+pub(crate) fn dependency_order(plugins: &[&dyn ErasedPlugin]) -> ConfigResult<Vec<&'static str>> {
+    fn visit(
+        plugin: &dyn ErasedPlugin,
+        plugins: &[&dyn ErasedPlugin],
+        path: &mut Vec<&'static str>,
+        ordered: &mut Vec<&'static str>,
+    ) -> ConfigResult<()> {
+        let name = plugin.section_name();
+        if ordered.contains(&name) {
+            return Ok(());
+        }
+        if path.contains(&name) {
+            path.push(name);
+            return Err(TylError::configuration(format!(
+                "Plugin dependency cycle: {}",
+                path.join(" -> ")
+            )));
+        }
+        path.push(name);
+        for dependency in plugin.section_dependencies() {
+            if let Some(dependency) = plugins.iter().find(|p| p.section_name() == dependency) {
+                visit(*dependency, plugins, path, ordered)?;
+            }
+        }
+        path.pop();
+        ordered.push(name);
+        Ok(())
+    }
+
+    let mut ordered = Vec::new();
+    for plugin in plugins {
+        visit(*plugin, plugins, &mut Vec::new(), &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RedisConfig;
+
+    #[test]
+    fn test_erased_round_trip() {
+        let mut plugin: Box<dyn ErasedPlugin> = Box::new(RedisConfig::default());
+        assert_eq!(plugin.section_name(), "redis");
+
+        let mut tree = plugin.to_value().unwrap();
+        *value_at_mut(&mut tree, &["database"], "redis.database").unwrap() = Value::from(4);
+        plugin.replace_from(tree).unwrap();
+
+        let redis = plugin.as_any().downcast_ref::<RedisConfig>().unwrap();
+        assert_eq!(redis.database, 4);
+    }
+
+    #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+    struct Node {
+        #[serde(skip)]
+        name: &'static str,
+        #[serde(skip)]
+        deps: Vec<&'static str>,
+    }
+
+    impl ConfigPlugin for Node {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        fn env_prefix(&self) -> &'static str {
+            ""
+        }
+        fn validate(&self) -> ConfigResult<()> {
+            Ok(())
+        }
+        fn load_from_env(&self) -> ConfigResult<Self> {
+            Ok(self.clone())
+        }
+        fn merge_env_from(&mut self, _env: &dyn Environment) -> ConfigResult<()> {
+            Ok(())
+        }
+        fn dependencies(&self) -> Vec<&'static str> {
+            self.deps.clone()
+        }
+    }
+
+    fn node(name: &'static str, deps: &[&'static str]) -> Node {
+        Node {
+            name,
+            deps: deps.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let (api, cache, db) = (
+            node("api", &["cache", "db", "missing"]),
+            node("cache", &["db"]),
+            node("db", &[]),
+        );
+        let plugins: [&dyn ErasedPlugin; 3] = [&api, &cache, &db];
+        assert_eq!(
+            dependency_order(&plugins).unwrap(),
+            vec!["db", "cache", "api"]
+        );
+
+        let (a, b) = (node("a", &["b"]), node("b", &["a"]));
+        let error = dependency_order(&[&a, &b]).unwrap_err();
+        assert!(error.to_string().contains("a -> b -> a"));
+    }
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(split_path("redis").unwrap(), ("redis", vec![]));
+        assert_eq!(
+            split_path("redis.database").unwrap(),
+            ("redis", vec!["database"])
+        );
+        assert!(split_path("").is_err());
+        assert!(split_path("redis..database").is_err());
+    }
+}
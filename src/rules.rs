@@ -0,0 +1,403 @@
+//! Declarative validation rules
+//!
+//! Plugins attach rules to their fields in `ConfigPlugin::field_metadata`
+//! and check them with [`validate`] from `ConfigPlugin::validate`:
+//!
+//! ```rust
+//! use tyl_config::rules::Rule;
+//! use tyl_config::FieldMetadata;
+//!
+//! let fields = vec![
+//!     FieldMetadata::new("url", "Connection URL").rule(Rule::url_scheme(["postgres", "postgresql"])),
+//!     FieldMetadata::new("port", "Server port").rule(Rule::port()),
+//!     FieldMetadata::new("password", "Password").rule(Rule::required_unless("url")),
+//!     FieldMetadata::new("pool_size", "Pooled connections").rule(Rule::range(1, 100)),
+//! ];
+//! ```
+//!
+//! The same rules end up in the JSON Schema (see `schema::apply_rules`),
+//! except `file_exists`, which only makes sense at runtime.
+//!
+//! A field that is unset (missing, `null` or `""`) only fails the presence
+//! rules (`non_empty`, `required_unless`, `required_with`); every other rule
+//! skips it.
+
+use crate::{ConfigPlugin, ConfigResult, FieldMetadata};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use tyl_errors::TylError;
+
+/// What a [`Rule`] checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleKind {
+    /// Number within the bounds, both inclusive
+    Range { min: Option<i64>, max: Option<i64> },
+    /// Set, and not an empty list or map
+    NonEmpty,
+    /// String matching a regular expression (unanchored)
+    Pattern(String),
+    /// DNS hostname or IP address
+    Hostname,
+    /// TCP/UDP port, 1 to 65535
+    Port,
+    /// URL whose scheme is one of these (case-insensitive)
+    UrlScheme(Vec<String>),
+    /// Path of an existing file or directory
+    FileExists,
+    /// One of these values
+    OneOf(Vec<Value>),
+    /// Set unless the named field of the same section is set
+    RequiredUnless(String),
+    /// Set when the named field of the same section is set
+    RequiredWith(String),
+}
+
+/// A check on one field, with an optional custom error message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub kind: RuleKind,
+    /// Replaces the generated message when the rule fails
+    pub message: Option<String>,
+}
+
+impl Rule {
+    fn new(kind: RuleKind) -> Self {
+        Self {
+            kind,
+            message: None,
+        }
+    }
+
+    pub fn range(min: i64, max: i64) -> Self {
+        Self::new(RuleKind::Range {
+            min: Some(min),
+            max: Some(max),
+        })
+    }
+
+    pub fn min(min: i64) -> Self {
+        Self::new(RuleKind::Range {
+            min: Some(min),
+            max: None,
+        })
+    }
+
+    pub fn max(max: i64) -> Self {
+        Self::new(RuleKind::Range {
+            min: None,
+            max: Some(max),
+        })
+    }
+
+    pub fn non_empty() -> Self {
+        Self::new(RuleKind::NonEmpty)
+    }
+
+    pub fn pattern(pattern: impl Into<String>) -> Self {
+        Self::new(RuleKind::Pattern(pattern.into()))
+    }
+
+    pub fn hostname() -> Self {
+        Self::new(RuleKind::Hostname)
+    }
+
+    pub fn port() -> Self {
+        Self::new(RuleKind::Port)
+    }
+
+    pub fn url_scheme<I, S>(schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::new(RuleKind::UrlScheme(
+            schemes.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    pub fn file_exists() -> Self {
+        Self::new(RuleKind::FileExists)
+    }
+
+    pub fn one_of<I, V>(values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        Self::new(RuleKind::OneOf(
+            values.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    pub fn required_unless(field: impl Into<String>) -> Self {
+        Self::new(RuleKind::RequiredUnless(field.into()))
+    }
+
+    pub fn required_with(field: impl Into<String>) -> Self {
+        Self::new(RuleKind::RequiredWith(field.into()))
+    }
+
+    /// Report `message` instead of the generated one
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Check `field` of a serialized section
+    pub fn check(&self, field: &str, section: &Map<String, Value>) -> Result<(), String> {
+        self.check_value(section.get(field), section)
+            .map_err(|message| self.message.clone().unwrap_or(message))
+    }
+
+    fn check_value(
+        &self,
+        value: Option<&Value>,
+        section: &Map<String, Value>,
+    ) -> Result<(), String> {
+        let field_set = |field: &str| section.get(field).is_some_and(is_set);
+        match &self.kind {
+            RuleKind::NonEmpty => {
+                let empty = match value {
+                    Some(Value::Array(items)) => items.is_empty(),
+                    Some(Value::Object(fields)) => fields.is_empty(),
+                    other => !other.is_some_and(is_set),
+                };
+                return if empty {
+                    Err("cannot be empty".to_string())
+                } else {
+                    Ok(())
+                };
+            }
+            RuleKind::RequiredUnless(other) => {
+                return if field_set(other) || value.is_some_and(is_set) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "cannot be empty (required when `{other}` is not set)"
+                    ))
+                };
+            }
+            RuleKind::RequiredWith(other) => {
+                return if !field_set(other) || value.is_some_and(is_set) {
+                    Ok(())
+                } else {
+                    Err(format!("cannot be empty (required when `{other}` is set)"))
+                };
+            }
+            _ => {}
+        }
+
+        let Some(value) = value.filter(|value| is_set(value)) else {
+            return Ok(());
+        };
+        match &self.kind {
+            RuleKind::Range { min, max } => {
+                let number = value.as_f64().ok_or("must be a number")?;
+                if let Some(min) = min.filter(|min| number < *min as f64) {
+                    return Err(format!("must be at least {min}"));
+                }
+                if let Some(max) = max.filter(|max| number > *max as f64) {
+                    return Err(format!("must be at most {max}"));
+                }
+                Ok(())
+            }
+            RuleKind::Port => match value.as_u64() {
+                Some(1..=65535) => Ok(()),
+                _ => Err("must be a port number (1-65535)".to_string()),
+            },
+            RuleKind::Pattern(pattern) => {
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|e| format!("has an invalid pattern `{pattern}`: {e}"))?;
+                if regex.is_match(as_str(value)?) {
+                    Ok(())
+                } else {
+                    Err(format!("must match `{pattern}`"))
+                }
+            }
+            RuleKind::Hostname => {
+                if is_hostname(as_str(value)?) {
+                    Ok(())
+                } else {
+                    Err("must be a hostname or IP address".to_string())
+                }
+            }
+            RuleKind::UrlScheme(schemes) => {
+                let scheme = as_str(value)?.split_once("://").map(|(scheme, _)| scheme);
+                if scheme.is_some_and(|scheme| {
+                    schemes
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+                }) {
+                    Ok(())
+                } else {
+                    Err(format!("must be a URL with scheme {}", schemes.join(", ")))
+                }
+            }
+            RuleKind::FileExists => {
+                let path = as_str(value)?;
+                if std::path::Path::new(path).exists() {
+                    Ok(())
+                } else {
+                    Err(format!("file `{path}` does not exist"))
+                }
+            }
+            RuleKind::OneOf(allowed) => {
+                if allowed.contains(value) {
+                    Ok(())
+                } else {
+                    let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                    Err(format!("must be one of {}", allowed.join(", ")))
+                }
+            }
+            RuleKind::NonEmpty | RuleKind::RequiredUnless(_) | RuleKind::RequiredWith(_) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RuleKind::Range { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "between {min} and {max}"),
+                (Some(min), None) => write!(f, "at least {min}"),
+                (None, Some(max)) => write!(f, "at most {max}"),
+                (None, None) => f.write_str("any number"),
+            },
+            RuleKind::NonEmpty => f.write_str("not empty"),
+            RuleKind::Pattern(pattern) => write!(f, "matches `{pattern}`"),
+            RuleKind::Hostname => f.write_str("hostname or IP address"),
+            RuleKind::Port => f.write_str("port number"),
+            RuleKind::UrlScheme(schemes) => write!(f, "URL with scheme {}", schemes.join(", ")),
+            RuleKind::FileExists => f.write_str("existing file"),
+            RuleKind::OneOf(values) => {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                write!(f, "one of {}", values.join(", "))
+            }
+            RuleKind::RequiredUnless(other) => write!(f, "required unless `{other}` is set"),
+            RuleKind::RequiredWith(other) => write!(f, "required when `{other}` is set"),
+        }
+    }
+}
+
+/// Every failed rule of `fields` on the serialized `config`
+pub fn check<T: Serialize + ?Sized>(
+    config: &T,
+    fields: &[FieldMetadata],
+) -> ConfigResult<Vec<TylError>> {
+    let value = serde_json::to_value(config)
+        .map_err(|e| TylError::serialization(format!("Failed to serialize config: {e}")))?;
+    let section = match value {
+        Value::Object(section) => section,
+        _ => Map::new(),
+    };
+    let mut errors = Vec::new();
+    for field in fields {
+        for rule in &field.rules {
+            if let Err(message) = rule.check(&field.name, &section) {
+                errors.push(TylError::validation(&field.name, message));
+            }
+        }
+    }
+    Ok(errors)
+}
+
+/// Check the rules declared in the plugin's field metadata, failing with the
+/// first violation
+pub fn validate<T: ConfigPlugin + Serialize>(config: &T) -> ConfigResult<()> {
+    match check(config, &config.field_metadata())?.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Not missing, `null` or `""`
+fn is_set(value: &Value) -> bool {
+    !matches!(value, Value::Null) && value.as_str() != Some("")
+}
+
+fn as_str(value: &Value) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| "must be a string".to_string())
+}
+
+fn is_hostname(host: &str) -> bool {
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return true;
+    }
+    let host = host.strip_suffix('.').unwrap_or(host);
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn section(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_value_rules() {
+        let values = section(json!({
+            "host": "db-1.internal",
+            "bad_host": "db_1",
+            "port": 0,
+            "size": 7,
+            "mode": "fast",
+            "url": "MySQL://db",
+            "blank": "",
+        }));
+
+        assert!(Rule::hostname().check("host", &values).is_ok());
+        assert!(Rule::hostname().check("bad_host", &values).is_err());
+        assert!(Rule::port().check("port", &values).is_err());
+        assert!(Rule::range(1, 10).check("size", &values).is_ok());
+        assert_eq!(
+            Rule::max(5).check("size", &values).unwrap_err(),
+            "must be at most 5"
+        );
+        assert!(Rule::one_of(["fast", "safe"])
+            .check("mode", &values)
+            .is_ok());
+        assert!(Rule::pattern("^[a-z]+$").check("mode", &values).is_ok());
+        assert!(Rule::url_scheme(["mysql"]).check("url", &values).is_ok());
+        assert!(Rule::url_scheme(["postgres"])
+            .check("url", &values)
+            .is_err());
+        assert!(Rule::file_exists().check("mode", &values).is_err());
+
+        // Unset values only fail presence rules
+        assert!(Rule::port().check("blank", &values).is_ok());
+        assert!(Rule::non_empty().check("blank", &values).is_err());
+        assert_eq!(
+            Rule::non_empty()
+                .message("is required")
+                .check("missing", &values)
+                .unwrap_err(),
+            "is required"
+        );
+    }
+
+    #[test]
+    fn test_cross_field_rules() {
+        let with_url = section(json!({ "url": "postgres://db", "password": "" }));
+        let without_url = section(json!({ "password": "" }));
+
+        let required = Rule::required_unless("url");
+        assert!(required.check("password", &with_url).is_ok());
+        assert!(required.check("password", &without_url).is_err());
+
+        let with = Rule::required_with("url");
+        assert!(with.check("password", &with_url).is_err());
+        assert!(with.check("password", &without_url).is_ok());
+    }
+}
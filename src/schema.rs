@@ -0,0 +1,240 @@
+//! JSON Schema generation
+//!
+//! Plugins have no schema annotations, so the schema is inferred from a
+//! serialized instance (normally the plugin defaults): every serialized key
+//! becomes a required property typed after its default value. Validation
+//! rules from the field metadata are added with [`apply_rules`].
+
+use crate::rules::RuleKind;
+use crate::FieldMetadata;
+use serde_json::{json, Map, Value};
+
+/// JSON Schema dialect emitted by this module
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Infer a schema for one value, recording it as the default
+pub fn infer_schema(value: &Value) -> Value {
+    let mut schema = match value {
+        Value::Null => json!({}),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_u64() => json!({ "type": "integer", "minimum": 0 }),
+        Value::Number(number) if number.is_i64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => match items.first() {
+            Some(first) => json!({ "type": "array", "items": infer_schema(first) }),
+            None => json!({ "type": "array" }),
+        },
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(key, field)| (key.clone(), infer_schema(field)))
+                .collect();
+            let required: Vec<&String> = fields.keys().collect();
+            return json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            });
+        }
+    };
+
+    if !value.is_null() {
+        schema["default"] = value.clone();
+    }
+    schema
+}
+
+/// Schema for a whole config document made of optional plugin sections
+pub fn document_schema(title: &str, sections: Vec<(String, Value)>) -> Value {
+    let properties: Map<String, Value> = sections
+        .into_iter()
+        .map(|(name, defaults)| (name, infer_schema(&defaults)))
+        .collect();
+
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": title,
+        "type": "object",
+        "properties": properties,
+    })
+}
+
+/// Add the validation rules of `fields` to an inferred object schema
+///
+/// Fields that are not serialized by default (unset `Option`s) get a
+/// property without becoming required. `file_exists` has no schema
+/// equivalent and is left out.
+pub fn apply_rules(schema: &mut Value, fields: &[FieldMetadata]) {
+    let mut conditions = Vec::new();
+    for field in fields.iter().filter(|field| !field.rules.is_empty()) {
+        let property = &mut schema["properties"][field.name.as_str()];
+        if property.is_null() {
+            *property = json!({});
+        }
+        let mut patterns = Vec::new();
+        for rule in &field.rules {
+            match &rule.kind {
+                RuleKind::Range { min, max } => {
+                    if let Some(min) = min {
+                        property["minimum"] = json!(min);
+                    }
+                    if let Some(max) = max {
+                        property["maximum"] = json!(max);
+                    }
+                }
+                RuleKind::NonEmpty if property["type"] == "array" => {
+                    property["minItems"] = json!(1)
+                }
+                RuleKind::NonEmpty if property["type"] == "object" => {
+                    property["minProperties"] = json!(1)
+                }
+                RuleKind::NonEmpty => property["minLength"] = json!(1),
+                RuleKind::Pattern(pattern) => patterns.push(pattern.clone()),
+                RuleKind::Hostname => {
+                    property["anyOf"] = json!([
+                        { "format": "hostname" },
+                        { "format": "ipv4" },
+                        { "format": "ipv6" },
+                    ])
+                }
+                RuleKind::Port => {
+                    property["minimum"] = json!(1);
+                    property["maximum"] = json!(65535);
+                }
+                RuleKind::UrlScheme(schemes) => {
+                    let schemes: Vec<String> =
+                        schemes.iter().map(|scheme| ignore_case(scheme)).collect();
+                    patterns.push(format!("^({})://", schemes.join("|")));
+                }
+                RuleKind::FileExists => {}
+                RuleKind::OneOf(values) => property["enum"] = json!(values),
+                RuleKind::RequiredUnless(other) => conditions.push(json!({
+                    "if": { "not": is_set(other) },
+                    "then": is_set(&field.name),
+                })),
+                RuleKind::RequiredWith(other) => conditions.push(json!({
+                    "if": is_set(other),
+                    "then": is_set(&field.name),
+                })),
+            }
+        }
+        // A property has a single `pattern`, several must all match
+        match patterns.as_slice() {
+            [] => {}
+            [pattern] => property["pattern"] = json!(pattern),
+            _ => {
+                let all: Vec<Value> = patterns
+                    .iter()
+                    .map(|pattern| json!({ "pattern": pattern }))
+                    .collect();
+                property["allOf"] = Value::Array(all);
+            }
+        }
+    }
+    if !conditions.is_empty() {
+        schema["allOf"] = Value::Array(conditions);
+    }
+}
+
+/// Regex matching `text` in any case, since JSON Schema patterns have no
+/// case-insensitive flag
+fn ignore_case(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphabetic() {
+                format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase())
+            } else {
+                regex::escape(&c.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Schema of a section whose `field` is present, not `null` and not `""`
+fn is_set(field: &str) -> Value {
+    json!({
+        "required": [field],
+        "properties": { field: { "not": { "enum": [null, ""] } } },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule;
+    use crate::{ConfigPlugin, PostgresConfig};
+
+    #[test]
+    fn test_infer_plugin_schema() {
+        let defaults = serde_json::to_value(PostgresConfig::default()).unwrap();
+        let schema = infer_schema(&defaults);
+
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["host"]["type"], "string");
+        assert_eq!(schema["properties"]["host"]["default"], "localhost");
+        assert_eq!(schema["properties"]["port"]["type"], "integer");
+        assert_eq!(schema["properties"]["port"]["minimum"], 0);
+
+        // `url` is skipped when unset, so it is neither described nor required
+        assert!(schema["properties"].get("url").is_none());
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&json!("password")));
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let defaults = serde_json::to_value(PostgresConfig::default()).unwrap();
+        let mut schema = infer_schema(&defaults);
+        apply_rules(&mut schema, &PostgresConfig::default().field_metadata());
+
+        assert_eq!(schema["properties"]["port"]["minimum"], 1);
+        assert_eq!(schema["properties"]["port"]["maximum"], 65535);
+        assert_eq!(schema["properties"]["pool_size"]["minimum"], 1);
+        // Case-insensitive, like the runtime check
+        let pattern = schema["properties"]["url"]["pattern"].as_str().unwrap();
+        let url = regex::Regex::new(pattern).unwrap();
+        assert!(url.is_match("postgresql://db/app") && url.is_match("POSTGRES://db/app"));
+        assert!(!url.is_match("mysql://db/app"));
+        // `url` is described now, but still not required
+        assert!(!schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("url")));
+
+        let conditions = schema["allOf"].as_array().unwrap();
+        assert!(conditions.contains(&json!({
+            "if": { "not": is_set("url") },
+            "then": is_set("password"),
+        })));
+    }
+
+    #[test]
+    fn test_patterns_of_one_field_all_apply() {
+        let fields = vec![FieldMetadata::new("url", "")
+            .rule(Rule::url_scheme(["redis"]))
+            .rule(Rule::pattern("/0$"))];
+        let mut schema = json!({ "type": "object", "properties": {} });
+        apply_rules(&mut schema, &fields);
+
+        let url = &schema["properties"]["url"];
+        assert!(url.get("pattern").is_none());
+        assert_eq!(
+            url["allOf"],
+            json!([{ "pattern": "^([rR][eE][dD][iI][sS])://" }, { "pattern": "/0$" }])
+        );
+    }
+
+    #[test]
+    fn test_document_schema_sections_are_optional() {
+        let schema = document_schema(
+            "OrderService",
+            vec![("db".to_string(), json!({ "host": "localhost" }))],
+        );
+
+        assert_eq!(schema["$schema"], SCHEMA_DIALECT);
+        assert_eq!(schema["title"], "OrderService");
+        assert!(schema.get("required").is_none());
+        assert_eq!(schema["properties"]["db"]["type"], "object");
+    }
+}
@@ -0,0 +1,560 @@
+//! Declarative service configuration
+//!
+//! [`service_config!`](crate::service_config) declares a service's whole
+//! configuration as a struct of plugins and generates the glue around
+//! [`ConfigManagerBuilder`] for it. Each field is a YAML section named after
+//! the field, so one service can hold several instances of the same plugin
+//! type.
+//!
+//! ```rust
+//! use tyl_config::{service_config, MapEnv, PostgresConfig, RedisConfig};
+//!
+//! service_config! {
+//!     /// Everything the order service needs
+//!     #[derive(Debug, Clone)]
+//!     pub struct OrderService {
+//!         pub db: PostgresConfig,
+//!         pub cache: RedisConfig,
+//!     }
+//! }
+//!
+//! let env = MapEnv::new().with("TYL_REDIS_HOST", "cache.internal");
+//! let config = OrderService::load_with_env("/nonexistent/order-service.yaml", env).unwrap();
+//! config.validate().unwrap();
+//!
+//! assert_eq!(config.cache.host, "cache.internal");
+//! assert_eq!(OrderService::SECTIONS, &["db", "cache"]);
+//! ```
+//!
+//! Loading goes through the builder, so migrations, deprecated keys, `.env`
+//! files, directories, `--set` overrides and validators all apply. For those,
+//! or to keep the [`ConfigManager`] for lint, policies, diffs, fingerprints
+//! or [`SharedConfig`](crate::SharedConfig), start from `builder()` and turn
+//! the manager into the struct with `from_manager`:
+//!
+//! ```rust
+//! # use tyl_config::{service_config, validation, MapEnv, PostgresConfig, RedisConfig};
+//! # service_config! {
+//! #     pub struct OrderService {
+//! #         pub db: PostgresConfig,
+//! #         pub cache: RedisConfig,
+//! #     }
+//! # }
+//! let manager = OrderService::builder()
+//!     .with_environment(MapEnv::new())
+//!     .with_validator("pools", validation::max_sum(["db", "cache"], "pool_size", 50))
+//!     .try_build()
+//!     .unwrap();
+//! manager.validate_all().unwrap();
+//!
+//! let config = OrderService::from_manager(&manager).unwrap();
+//! assert_eq!(config.db.pool_size, 10);
+//! ```
+//!
+//! Every field type must implement `ConfigPlugin`, `Default`, `Clone`,
+//! `Serialize` and `Deserialize`. Env vars are read by the plugin itself, so
+//! two fields of the same plugin type see the same variables. A field named
+//! `postgres` or `redis` must hold the built-in plugin of that name.
+
+use crate::{
+    registry, template, ConfigManager, ConfigManagerBuilder, ConfigPlugin, ConfigResult,
+    Environment, FieldMetadata, PostgresConfig, RedisConfig,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+use tyl_errors::TylError;
+
+/// Section name of a `service_config!` field, implemented by a marker type
+/// generated for each field
+#[doc(hidden)]
+pub trait SectionName: 'static {
+    const NAME: &'static str;
+}
+
+/// A plugin registered under the name of a service field instead of its own
+#[doc(hidden)]
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Section<T, N> {
+    config: T,
+    #[serde(skip)]
+    name: PhantomData<fn() -> N>,
+}
+
+impl<T, N> Section<T, N> {
+    fn new(config: T) -> Self {
+        Self {
+            config,
+            name: PhantomData,
+        }
+    }
+}
+
+impl<T: Default, N> Default for Section<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Clone, N> Clone for Section<T, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.config.clone())
+    }
+}
+
+impl<T: fmt::Debug, N> fmt::Debug for Section<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.config.fmt(f)
+    }
+}
+
+impl<T: ConfigPlugin, N: SectionName> ConfigPlugin for Section<T, N> {
+    fn name(&self) -> &'static str {
+        N::NAME
+    }
+
+    fn env_prefix(&self) -> &'static str {
+        self.config.env_prefix()
+    }
+
+    fn validate(&self) -> ConfigResult<()> {
+        self.config.validate()
+    }
+
+    fn load_from_env(&self) -> ConfigResult<Self> {
+        Ok(Self::new(self.config.load_from_env()?))
+    }
+
+    fn merge_env_from(&mut self, env: &dyn Environment) -> ConfigResult<()> {
+        self.config.merge_env_from(env)
+    }
+
+    fn field_metadata(&self) -> Vec<FieldMetadata> {
+        self.config.field_metadata()
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        self.config.dependencies()
+    }
+
+    fn migrations(&self) -> Vec<crate::migration::Migration> {
+        self.config.migrations()
+    }
+}
+
+/// Register `config` as the section `N` of a service
+///
+/// The built-in plugins keep their own slot when the field is named after
+/// them, everything else becomes a custom plugin named after the field.
+#[doc(hidden)]
+pub fn register<T, N>(builder: ConfigManagerBuilder, config: T) -> ConfigManagerBuilder
+where
+    T: ConfigPlugin + Default + Serialize + DeserializeOwned + Clone + 'static,
+    N: SectionName,
+{
+    let any = &config as &dyn Any;
+    if let ("postgres", Some(postgres)) = (N::NAME, any.downcast_ref::<PostgresConfig>()) {
+        return builder.with_postgres(postgres.clone());
+    }
+    if let ("redis", Some(redis)) = (N::NAME, any.downcast_ref::<RedisConfig>()) {
+        return builder.with_redis(redis.clone());
+    }
+    builder.with_plugin(Section::<T, N>::new(config))
+}
+
+/// The section `N` of a service, as registered by [`register`]
+#[doc(hidden)]
+pub fn section<T, N>(config: &ConfigManager) -> ConfigResult<T>
+where
+    T: Clone + 'static,
+    N: SectionName,
+{
+    let plugin = config
+        .section(N::NAME)
+        .ok_or_else(|| registry::unknown_section(N::NAME))?;
+    let any = plugin.as_any();
+    any.downcast_ref::<Section<T, N>>()
+        .map(|section| section.config.clone())
+        .or_else(|| any.downcast_ref::<T>().cloned())
+        .ok_or_else(|| {
+            TylError::configuration(format!(
+                "Section `{}` is not a {}",
+                N::NAME,
+                std::any::type_name::<T>()
+            ))
+        })
+}
+
+/// Write the template of `config`'s plugins, titled after the service
+pub fn write_template(output_path: &str, title: &str, config: &ConfigManager) -> ConfigResult<()> {
+    template::write_atomic(output_path, &config.template_content(title)?)
+}
+
+/// Declare a service configuration made of plugins
+///
+/// See the [`service`](crate::service) module for an example. Generated items:
+///
+/// - `Default`, built from every plugin's defaults
+/// - `SECTIONS`: YAML section names, in declaration order
+/// - `builder()`: a `ConfigManagerBuilder` with every section registered at
+///   its defaults, and `from_manager(&manager)` to read the sections back
+/// - `to_manager()`: a `ConfigManager` holding the current values
+/// - `load(path)` / `load_with_env(path, env)`: YAML file (optional) + env vars
+/// - `from_env(env)`
+/// - `validate()`: every plugin, all errors together
+/// - `to_yaml_value()` / `generate_config_template(path)`
+/// - `json_schema()`: inferred from the plugin defaults, with their field rules
+#[macro_export]
+macro_rules! service_config {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )+
+        }
+
+        impl ::std::default::Default for $name {
+            fn default() -> Self {
+                Self {
+                    $( $field: <$ty as ::std::default::Default>::default(), )+
+                }
+            }
+        }
+
+        const _: () = {
+            // One marker type per field, naming its section
+            mod sections {
+                $(
+                    #[allow(non_camel_case_types)]
+                    pub struct $field;
+
+                    impl $crate::service::SectionName for $field {
+                        const NAME: &'static str = stringify!($field);
+                    }
+                )+
+            }
+
+            // Services rarely use every generated item
+            #[allow(dead_code)]
+            impl $name {
+                /// YAML section names, one per plugin field
+                pub const SECTIONS: &'static [&'static str] = &[$( stringify!($field) ),+];
+
+                /// A builder with every section registered at its defaults
+                pub fn builder() -> $crate::ConfigManagerBuilder {
+                    Self::default().register($crate::ConfigManager::builder())
+                }
+
+                /// Register every section with its current value
+                fn register(
+                    &self,
+                    builder: $crate::ConfigManagerBuilder,
+                ) -> $crate::ConfigManagerBuilder {
+                    $(
+                        let builder = $crate::service::register::<$ty, sections::$field>(
+                            builder,
+                            ::std::clone::Clone::clone(&self.$field),
+                        );
+                    )+
+                    builder
+                }
+
+                /// The sections of a manager built from `builder()`
+                pub fn from_manager(config: &$crate::ConfigManager) -> $crate::ConfigResult<Self> {
+                    Ok(Self {
+                        $( $field: $crate::service::section::<$ty, sections::$field>(config)?, )+
+                    })
+                }
+
+                /// A manager holding the current values, without env vars or files
+                pub fn to_manager(&self) -> $crate::ConfigResult<$crate::ConfigManager> {
+                    self.register(
+                            $crate::ConfigManager::builder()
+                                .with_environment($crate::MapEnv::new()),
+                        )
+                        .try_build()
+                        .map_err($crate::__private::TylError::from)
+                }
+
+                /// Load from a YAML file (skipped if missing) and the process environment
+                pub fn load(yaml_path: &str) -> ::std::result::Result<Self, $crate::ConfigErrors> {
+                    Self::load_with_env(yaml_path, $crate::ProcessEnv)
+                }
+
+                /// Load from a YAML file (skipped if missing) and the given environment
+                pub fn load_with_env(
+                    yaml_path: &str,
+                    env: impl $crate::Environment + 'static,
+                ) -> ::std::result::Result<Self, $crate::ConfigErrors> {
+                    let builder = Self::builder()
+                        .with_environment(env)
+                        .with_yaml_file(yaml_path)
+                        .map_err(|e| {
+                            let mut errors = $crate::ConfigErrors::new();
+                            errors.push(yaml_path, e);
+                            errors
+                        })?;
+                    Self::finish(builder)
+                }
+
+                /// Load from defaults and env vars only
+                pub fn from_env(
+                    env: impl $crate::Environment + 'static,
+                ) -> ::std::result::Result<Self, $crate::ConfigErrors> {
+                    Self::finish(Self::builder().with_environment(env))
+                }
+
+                fn finish(
+                    builder: $crate::ConfigManagerBuilder,
+                ) -> ::std::result::Result<Self, $crate::ConfigErrors> {
+                    Self::from_manager(&builder.try_build()?).map_err(|e| {
+                        let mut errors = $crate::ConfigErrors::new();
+                        errors.push(stringify!($name), e);
+                        errors
+                    })
+                }
+
+                /// Validate every plugin, reporting all failures together
+                pub fn validate(&self) -> ::std::result::Result<(), $crate::ConfigErrors> {
+                    let mut errors = $crate::ConfigErrors::new();
+                    $(
+                        if let Err(e) = $crate::ConfigPlugin::validate(&self.$field) {
+                            errors.push(stringify!($field), e);
+                        }
+                    )+
+                    errors.into_result()
+                }
+
+                /// The resolved configuration as a YAML document
+                pub fn to_yaml_value(
+                    &self,
+                ) -> $crate::ConfigResult<$crate::__private::serde_yaml::Value> {
+                    let mut document = $crate::__private::serde_yaml::Mapping::new();
+                    $(
+                        document.insert(
+                            stringify!($field).into(),
+                            $crate::__private::serde_yaml::to_value(&self.$field).map_err(|e| {
+                                $crate::__private::TylError::serialization(format!(
+                                    "Failed to serialize {} config: {e}",
+                                    stringify!($field)
+                                ))
+                            })?,
+                        );
+                    )+
+                    Ok($crate::__private::serde_yaml::Value::Mapping(document))
+                }
+
+                /// Write the resolved configuration as a documented YAML template
+                pub fn generate_config_template(&self, output_path: &str) -> $crate::ConfigResult<()> {
+                    $crate::service::write_template(
+                        output_path,
+                        stringify!($name),
+                        &self.to_manager()?,
+                    )
+                }
+
+                /// JSON Schema for the config file, inferred from the plugin defaults
+                /// and field rules
+                pub fn json_schema() -> $crate::ConfigResult<$crate::__private::serde_json::Value> {
+                    Self::default().to_manager()?.json_schema(stringify!($name))
+                }
+            }
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConfigPlugin, ConfigResult, Environment, MapEnv, PostgresConfig, RedisConfig};
+    use serde::{Deserialize, Serialize};
+    use tyl_errors::TylError;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct ApiConfig {
+        base_url: String,
+        timeout_ms: u64,
+    }
+
+    impl Default for ApiConfig {
+        fn default() -> Self {
+            Self {
+                base_url: "http://localhost:8080".to_string(),
+                timeout_ms: 5000,
+            }
+        }
+    }
+
+    impl ConfigPlugin for ApiConfig {
+        fn name(&self) -> &'static str {
+            "api"
+        }
+        fn env_prefix(&self) -> &'static str {
+            "API"
+        }
+        fn validate(&self) -> ConfigResult<()> {
+            if self.base_url.is_empty() {
+                return Err(TylError::validation("base_url", "cannot be empty"));
+            }
+            Ok(())
+        }
+        fn load_from_env(&self) -> ConfigResult<Self> {
+            let mut config = Self::default();
+            config.merge_env()?;
+            Ok(config)
+        }
+        fn merge_env_from(&mut self, env: &dyn Environment) -> ConfigResult<()> {
+            if let Some(base_url) = env.var("API_BASE_URL") {
+                self.base_url = base_url;
+            }
+            Ok(())
+        }
+    }
+
+    crate::service_config! {
+        #[derive(Debug, Clone)]
+        struct OrderService {
+            db: PostgresConfig,
+            cache: RedisConfig,
+            api: ApiConfig,
+        }
+    }
+
+    fn temp_yaml(content: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tyl-config-service-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_service_config_loads_sections_and_env() {
+        let path = temp_yaml("api:\n  base_url: https://payments.internal\n  timeout_ms: 250\n");
+        let env = MapEnv::new().with("TYL_POSTGRES_HOST", "orders-db");
+
+        let config = OrderService::load_with_env(path.to_str().unwrap(), env).unwrap();
+
+        assert_eq!(config.db.host, "orders-db");
+        assert_eq!(config.cache.port, 6379);
+        assert_eq!(config.api.base_url, "https://payments.internal");
+        assert_eq!(config.api.timeout_ms, 250);
+        assert!(config.validate().is_ok());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_service_config_collects_errors() {
+        let path = temp_yaml("api:\n  base_url: [not, a, string]\n");
+        let env = MapEnv::new().with("TYL_REDIS_PORT", "abc");
+
+        let errors = OrderService::load_with_env(path.to_str().unwrap(), env).unwrap_err();
+        let sections: Vec<&str> = errors.iter().map(|(section, _)| section).collect();
+        assert_eq!(sections, vec!["api", "cache"]);
+
+        let mut config = OrderService::default();
+        config.db.host = String::new();
+        config.api.base_url = String::new();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_service_config_through_manager() {
+        // Sources of the builder apply to the service sections
+        let mount =
+            std::env::temp_dir().join(format!("tyl-config-service-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&mount).unwrap();
+        std::fs::write(mount.join("cache.port"), "6380").unwrap();
+        std::fs::write(mount.join("db.pool_size"), "3").unwrap();
+
+        let manager = OrderService::builder()
+            .with_environment(MapEnv::new().with("API_BASE_URL", "https://api.internal"))
+            .with_directory(&mount)
+            .unwrap()
+            .try_build()
+            .unwrap();
+        assert_eq!(manager.section_names(), ["db", "cache", "api"]);
+        assert_eq!(manager.get::<u16>("cache.port").unwrap(), 6380);
+
+        let config = OrderService::from_manager(&manager).unwrap();
+        assert_eq!(config.cache.port, 6380);
+        assert_eq!(config.db.pool_size, 3);
+        assert_eq!(config.api.base_url, "https://api.internal");
+
+        // Back to a manager holding the same values
+        let manager = config.to_manager().unwrap();
+        assert_eq!(manager.get::<u32>("db.pool_size").unwrap(), 3);
+
+        let _ = std::fs::remove_dir_all(mount);
+    }
+
+    crate::service_config! {
+        struct BuiltinService {
+            postgres: PostgresConfig,
+            redis: RedisConfig,
+        }
+    }
+
+    #[test]
+    fn test_builtin_section_names() {
+        let manager = BuiltinService::builder()
+            .with_environment(MapEnv::new().with("TYL_REDIS_HOST", "cache"))
+            .try_build()
+            .unwrap();
+        assert_eq!(manager.redis().unwrap().host, "cache");
+        let config = BuiltinService::from_manager(&manager).unwrap();
+        assert_eq!(config.redis.host, "cache");
+        assert_eq!(config.postgres.port, 5432);
+    }
+
+    #[test]
+    fn test_service_config_template_and_schema() {
+        let config = OrderService::from_env(MapEnv::new()).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "tyl-config-service-template-{}.yaml",
+            uuid::Uuid::new_v4()
+        ));
+        let temp_path = path.to_str().unwrap();
+        config.generate_config_template(temp_path).unwrap();
+        let content = std::fs::read_to_string(temp_path).unwrap();
+        assert!(content.contains("# OrderService Configuration Template"));
+        assert!(content.contains("db:"));
+        assert!(content.contains("cache:"));
+        assert!(content.contains("base_url: http://localhost:8080"));
+        // Keys are documented from the plugins' field metadata
+        assert!(content.contains("  # Env: TYL_POSTGRES_HOST, PGHOST\n  host: localhost\n"));
+
+        // The template loads back into the same configuration
+        let reloaded = OrderService::load_with_env(temp_path, MapEnv::new()).unwrap();
+        assert_eq!(reloaded.api.timeout_ms, 5000);
+        let _ = std::fs::remove_file(temp_path);
+
+        let schema = OrderService::json_schema().unwrap();
+        assert_eq!(schema["title"], "OrderService");
+        assert_eq!(
+            schema["properties"]["api"]["properties"]["timeout_ms"]["type"],
+            "integer"
+        );
+        // Field rules of the plugins are part of the schema
+        assert_eq!(
+            schema["properties"]["db"]["properties"]["port"]["maximum"],
+            65535
+        );
+    }
+}
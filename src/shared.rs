@@ -0,0 +1,613 @@
+//! Lock-free live configuration
+//!
+//! [`SharedConfig`] holds the current `Arc<ConfigManager>` in an
+//! [`ArcSwap`]: readers load a snapshot without taking a lock, a writer
+//! swaps in a new validated version. Clones share the same slot.
+//!
+//! ```rust
+//! use tyl_config::{shared::SharedConfig, ConfigManager, MapEnv, RedisConfig};
+//!
+//! let builder = || {
+//!     ConfigManager::builder()
+//!         .with_environment(MapEnv::new())
+//!         .with_redis(RedisConfig::default())
+//! };
+//! let shared = SharedConfig::new(builder().build()).unwrap();
+//! let redis = shared.live::<RedisConfig>().unwrap();
+//! assert_eq!(redis.get().port, 6379);
+//!
+//! let mut next = builder().build();
+//! next.set("redis.port", 6380).unwrap();
+//! shared.store(next).unwrap();
+//! assert_eq!(redis.get().port, 6380);
+//! ```
+//!
+//! A [`Live`] view projects one plugin out of whatever version is current.
+//! To keep views valid, a new version may not drop a section the current one
+//! has, or fill it with a different plugin type.
+//!
+//! ## Change Hooks
+//!
+//! [`SharedConfig::on_change`] registers a hook that runs before a new
+//! version is published, only if its plugin changed. Hooks run in plugin
+//! dependency order (see `ConfigPlugin::dependencies`) and get the old and
+//! new plugin plus the plugin's redacted [`ConfigDiff`]. A hook that returns
+//! an error rejects the version: hooks that already ran are called again
+//! from new back to old, in reverse order, and the current version stays.
+//!
+//! ```rust
+//! use tyl_config::{shared::SharedConfig, ConfigManager, MapEnv, RedisConfig};
+//!
+//! let builder = |port: &str| {
+//!     ConfigManager::builder()
+//!         .with_environment(MapEnv::new().with("TYL_REDIS_PORT", port))
+//!         .with_redis(RedisConfig::default())
+//! };
+//! let shared = SharedConfig::from_builder(builder("6379")).unwrap();
+//! shared
+//!     .on_change::<RedisConfig>(|_old, new, diff| {
+//!         println!("rebuilding pool for {}: {}", new.host, diff.to_text());
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//!
+//! shared.reload(builder("6380")).unwrap();
+//! ```
+
+use crate::audit::{AuditEntry, AuditLog, Trigger};
+use crate::{
+    ConfigDiff, ConfigManager, ConfigManagerBuilder, ConfigPlugin, ConfigResult, PluginSlot,
+};
+use arc_swap::ArcSwap;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tyl_errors::TylError;
+
+/// Erased change hook: old config, new config, diff of its section
+type HookFn = dyn Fn(&ConfigManager, &ConfigManager, &ConfigDiff) -> ConfigResult<()> + Send + Sync;
+
+struct Hook {
+    section: &'static str,
+    run: Box<HookFn>,
+}
+
+/// Registered hooks and the audit trail; the lock also serializes writers
+#[derive(Default)]
+struct Writer {
+    hooks: Vec<Arc<Hook>>,
+    audit: AuditLog,
+}
+
+impl std::fmt::Debug for Writer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections: Vec<&str> = self.hooks.iter().map(|hook| hook.section).collect();
+        f.debug_struct("Writer")
+            .field("hooks", &sections)
+            .field("audit", &self.audit)
+            .finish()
+    }
+}
+
+/// Current configuration, shared between readers and writers
+#[derive(Debug, Clone)]
+pub struct SharedConfig {
+    current: Arc<ArcSwap<ConfigManager>>,
+    writer: Arc<Mutex<Writer>>,
+}
+
+impl SharedConfig {
+    /// Share `config`, failing if it does not validate
+    ///
+    /// `config` is recorded as the [`Trigger::Startup`] audit entry.
+    pub fn new(config: ConfigManager) -> ConfigResult<Self> {
+        config.validate()?;
+        let mut writer = Writer::default();
+        let diff = config.diff_from_empty()?;
+        writer.audit.record(Trigger::Startup, &config, diff)?;
+        Ok(Self {
+            current: Arc::new(ArcSwap::from_pointee(config)),
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// Keep at most `limit` audit entries (default
+    /// [`DEFAULT_HISTORY_LIMIT`](crate::audit::DEFAULT_HISTORY_LIMIT))
+    pub fn with_history_limit(self, limit: usize) -> Self {
+        self.lock().audit.set_limit(limit);
+        self
+    }
+
+    /// Append audit entries to a JSON-lines file: the kept ones not written
+    /// to a file yet, then every new one
+    ///
+    /// Later write errors are logged; they do not fail a reload.
+    pub fn with_audit_file(self, path: impl Into<std::path::PathBuf>) -> ConfigResult<Self> {
+        self.lock().audit.set_file(path.into())?;
+        Ok(self)
+    }
+
+    /// The recorded versions, oldest first
+    pub fn history(&self) -> Vec<AuditEntry> {
+        self.lock().audit.entries()
+    }
+
+    /// Build, validate and share a configuration
+    pub fn from_builder(builder: ConfigManagerBuilder) -> ConfigResult<Self> {
+        Self::new(builder.try_build()?)
+    }
+
+    /// The current version
+    pub fn load(&self) -> Arc<ConfigManager> {
+        self.current.load_full()
+    }
+
+    /// Swap in a new version, returning the old one
+    ///
+    /// Fails, leaving the current version in place, if `config` does not
+    /// validate, drops a section of the current version, changes its plugin
+    /// type or is rejected by a change hook.
+    pub fn store(&self, config: ConfigManager) -> ConfigResult<Arc<ConfigManager>> {
+        self.store_with(config, Trigger::Api)
+    }
+
+    /// [`store`](Self::store), recording `trigger` in the audit trail
+    ///
+    /// A version without changes is published but not recorded.
+    pub fn store_with(
+        &self,
+        config: ConfigManager,
+        trigger: Trigger,
+    ) -> ConfigResult<Arc<ConfigManager>> {
+        config.validate()?;
+        let mut writer = self.lock();
+        let current = self.load();
+        let sections = config.section_types();
+        for (name, plugin_type) in current.section_types() {
+            match sections.iter().find(|(section, _)| *section == name) {
+                None => {
+                    return Err(TylError::configuration(format!(
+                        "New configuration drops the `{name}` section"
+                    )))
+                }
+                Some((_, new_type)) if *new_type != plugin_type => {
+                    return Err(TylError::configuration(format!(
+                        "New configuration changes the plugin type of the `{name}` section"
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+
+        let diff = current.diff(&config)?;
+        run_hooks(&writer.hooks, &current, &config, &diff)?;
+        if !diff.is_empty() {
+            if let Err(e) = writer.audit.record(trigger, &config, diff) {
+                log::error!("Failed to record configuration change: {e}");
+            }
+        }
+        Ok(self.current.swap(Arc::new(config)))
+    }
+
+    /// Build a new version and [`store`](Self::store) it
+    pub fn reload(&self, builder: ConfigManagerBuilder) -> ConfigResult<Arc<ConfigManager>> {
+        self.store(builder.try_build()?)
+    }
+
+    /// Re-run the sources of the current version (see
+    /// [`ConfigManager::rebuild`]) and [`store`](Self::store) the result
+    pub fn rebuild(&self) -> ConfigResult<Arc<ConfigManager>> {
+        self.rebuild_with(Trigger::Api)
+    }
+
+    /// [`rebuild`](Self::rebuild), recording `trigger` in the audit trail
+    pub fn rebuild_with(&self, trigger: Trigger) -> ConfigResult<Arc<ConfigManager>> {
+        self.store_with(self.load().rebuild()?, trigger)
+    }
+
+    /// Call `hook(old, new, diff)` before publishing a version in which
+    /// plugin `T` changed
+    ///
+    /// Returning an error rejects the version. Hooks run while the writer
+    /// lock is held, so they must not store or register hooks themselves.
+    /// Fails if `T` is not configured.
+    pub fn on_change<T: ConfigPlugin + 'static>(
+        &self,
+        hook: impl Fn(&T, &T, &ConfigDiff) -> ConfigResult<()> + Send + Sync + 'static,
+    ) -> ConfigResult<()> {
+        let section = configured::<T>(&self.load())?.name();
+        let run = move |old: &ConfigManager, new: &ConfigManager, diff: &ConfigDiff| match (
+            old.plugin::<T>(),
+            new.plugin::<T>(),
+        ) {
+            (Some(old), Some(new)) => hook(old, new, diff),
+            _ => Ok(()),
+        };
+        self.lock().hooks.push(Arc::new(Hook {
+            section,
+            run: Box::new(run),
+        }));
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Writer> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A view of plugin `T` that follows every new version
+    ///
+    /// Fails if `T` is not configured.
+    pub fn live<T: ConfigPlugin + 'static>(&self) -> ConfigResult<Live<T>> {
+        configured::<T>(&self.load())?;
+        Ok(Live {
+            shared: self.clone(),
+            plugin: PhantomData,
+        })
+    }
+}
+
+fn configured<T: ConfigPlugin + 'static>(config: &ConfigManager) -> ConfigResult<&T> {
+    config.plugin::<T>().ok_or_else(|| {
+        TylError::configuration(format!(
+            "Plugin `{}` is not configured",
+            std::any::type_name::<T>()
+        ))
+    })
+}
+
+/// Run the hooks of changed sections in dependency order, undoing the ones
+/// that ran if a hook fails
+fn run_hooks(
+    hooks: &[Arc<Hook>],
+    old: &ConfigManager,
+    new: &ConfigManager,
+    diff: &ConfigDiff,
+) -> ConfigResult<()> {
+    if hooks.is_empty() || diff.is_empty() {
+        return Ok(());
+    }
+
+    let mut ran: Vec<(&Hook, ConfigDiff)> = Vec::new();
+    for section in new.dependency_order()? {
+        let section_diff = diff.for_section(section);
+        if section_diff.is_empty() {
+            continue;
+        }
+        for hook in hooks.iter().filter(|hook| hook.section == section) {
+            if let Err(e) = (hook.run)(old, new, &section_diff) {
+                for (hook, diff) in ran.iter().rev() {
+                    // Best effort: the reload fails with the original error either way
+                    let _ = (hook.run)(new, old, &diff.reversed());
+                }
+                return Err(TylError::configuration(format!(
+                    "Change to `{section}` rejected: {e}"
+                )));
+            }
+            ran.push((hook, section_diff.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// One plugin of a [`SharedConfig`], always at the latest version
+pub struct Live<T> {
+    shared: SharedConfig,
+    plugin: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Live<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            plugin: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Live<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Live")
+            .field("plugin", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T: ConfigPlugin + 'static> Live<T> {
+    /// The plugin in the current version
+    ///
+    /// The returned snapshot keeps that version alive; call `get` again to
+    /// see later ones.
+    pub fn get(&self) -> Snapshot<T> {
+        let config = self.shared.load();
+        let slot = config
+            .plugin_slot::<T>()
+            .expect("stored versions keep the plugin type of every section");
+        Snapshot {
+            config,
+            slot,
+            plugin: PhantomData,
+        }
+    }
+}
+
+/// A plugin borrowed from one configuration version
+///
+/// The plugin is located once, when the snapshot is taken.
+pub struct Snapshot<T> {
+    config: Arc<ConfigManager>,
+    slot: PluginSlot,
+    plugin: PhantomData<fn() -> T>,
+}
+
+impl<T: ConfigPlugin + 'static> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.config
+            .plugin_at::<T>(self.slot)
+            .expect("the slot was resolved against this version")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapEnv, PostgresConfig, RedisConfig};
+
+    fn redis(port: &str) -> ConfigManagerBuilder {
+        ConfigManager::builder()
+            .with_environment(MapEnv::new().with("TYL_REDIS_PORT", port))
+            .with_redis(RedisConfig::default())
+    }
+
+    #[test]
+    fn test_live_view_follows_store() {
+        let shared = SharedConfig::from_builder(redis("6379")).unwrap();
+        let live = shared.live::<RedisConfig>().unwrap();
+        let before = live.get();
+
+        let previous = shared.reload(redis("6380")).unwrap();
+        assert_eq!(previous.redis().unwrap().port, 6379);
+        assert_eq!(before.port, 6379);
+        assert_eq!(live.get().port, 6380);
+        assert_eq!(live.clone().get().port, 6380);
+
+        assert!(shared.live::<PostgresConfig>().is_err());
+    }
+
+    #[test]
+    fn test_store_rejects_invalid_versions() {
+        let shared = SharedConfig::from_builder(redis("6379")).unwrap();
+
+        let invalid = ConfigManager::builder()
+            .with_environment(MapEnv::new().with("TYL_REDIS_POOL_SIZE", "0"))
+            .with_redis(RedisConfig::default())
+            .build();
+        assert!(shared.store(invalid).is_err());
+
+        let empty = ConfigManager::builder()
+            .with_environment(MapEnv::new())
+            .build();
+        assert!(shared.store(empty).is_err());
+        assert_eq!(shared.load().redis().unwrap().port, 6379);
+    }
+
+    #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+    struct Pool {
+        size: u32,
+    }
+
+    impl ConfigPlugin for Pool {
+        fn name(&self) -> &'static str {
+            "pool"
+        }
+        fn env_prefix(&self) -> &'static str {
+            "TYL_POOL"
+        }
+        fn validate(&self) -> ConfigResult<()> {
+            Ok(())
+        }
+        fn load_from_env(&self) -> ConfigResult<Self> {
+            Ok(self.clone())
+        }
+        fn merge_env_from(&mut self, env: &dyn crate::Environment) -> ConfigResult<()> {
+            if let Some(size) = env.var("TYL_POOL_SIZE") {
+                self.size = size.parse().unwrap();
+            }
+            Ok(())
+        }
+        fn dependencies(&self) -> Vec<&'static str> {
+            vec!["redis"]
+        }
+    }
+
+    fn pool_and_redis(size: &str, port: &str) -> ConfigManagerBuilder {
+        ConfigManager::builder()
+            .with_environment(
+                MapEnv::new()
+                    .with("TYL_POOL_SIZE", size)
+                    .with("TYL_REDIS_PORT", port),
+            )
+            .with_plugin(Pool::default())
+            .with_redis(RedisConfig::default())
+    }
+
+    /// Same section name as [`Pool`], different type
+    #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+    struct OtherPool {
+        size: u32,
+    }
+
+    impl ConfigPlugin for OtherPool {
+        fn name(&self) -> &'static str {
+            "pool"
+        }
+        fn env_prefix(&self) -> &'static str {
+            "TYL_POOL"
+        }
+        fn validate(&self) -> ConfigResult<()> {
+            Ok(())
+        }
+        fn load_from_env(&self) -> ConfigResult<Self> {
+            Ok(self.clone())
+        }
+        fn merge_env_from(&mut self, _env: &dyn crate::Environment) -> ConfigResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_store_rejects_changed_section_type() {
+        let shared = SharedConfig::from_builder(pool_and_redis("1", "6379")).unwrap();
+        let live = shared.live::<Pool>().unwrap();
+
+        let swapped = ConfigManager::builder()
+            .with_environment(MapEnv::new())
+            .with_plugin(OtherPool::default())
+            .with_redis(RedisConfig::default())
+            .build();
+        let error = shared.store(swapped).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("plugin type of the `pool` section"));
+        assert_eq!(live.get().size, 1);
+    }
+
+    #[test]
+    fn test_change_hooks_in_dependency_order() {
+        let shared = SharedConfig::from_builder(pool_and_redis("1", "6379")).unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let log = calls.clone();
+        shared
+            .on_change::<Pool>(move |old, new, diff| {
+                assert_eq!(diff.changes.len(), 1);
+                log.lock()
+                    .unwrap()
+                    .push(format!("pool {} -> {}", old.size, new.size));
+                Ok(())
+            })
+            .unwrap();
+        let log = calls.clone();
+        shared
+            .on_change::<RedisConfig>(move |old, new, diff| {
+                assert!(diff.changes.iter().all(|c| c.section == "redis"));
+                log.lock()
+                    .unwrap()
+                    .push(format!("redis {} -> {}", old.port, new.port));
+                Ok(())
+            })
+            .unwrap();
+
+        shared.reload(pool_and_redis("2", "6380")).unwrap();
+        shared.reload(pool_and_redis("3", "6380")).unwrap();
+        shared.reload(pool_and_redis("3", "6380")).unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["redis 6379 -> 6380", "pool 1 -> 2", "pool 2 -> 3"]
+        );
+    }
+
+    #[test]
+    fn test_rejected_change_rolls_back() {
+        let shared = SharedConfig::from_builder(pool_and_redis("1", "6379")).unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let log = calls.clone();
+        shared
+            .on_change::<RedisConfig>(move |old, new, _| {
+                log.lock()
+                    .unwrap()
+                    .push(format!("redis {} -> {}", old.port, new.port));
+                Ok(())
+            })
+            .unwrap();
+        shared
+            .on_change::<Pool>(|_, new, _| {
+                if new.size > 10 {
+                    return Err(TylError::validation("size", "pool too large"));
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let error = shared.reload(pool_and_redis("50", "6380")).unwrap_err();
+        assert!(error.to_string().contains("pool too large"));
+        assert_eq!(shared.load().redis().unwrap().port, 6379);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["redis 6379 -> 6380", "redis 6380 -> 6379"]
+        );
+    }
+
+    #[test]
+    fn test_audit_history() {
+        let dir = std::env::temp_dir().join(format!("tyl-config-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let audit_file = dir.join("audit.jsonl");
+
+        let shared = SharedConfig::from_builder(redis("6379"))
+            .unwrap()
+            .with_history_limit(3)
+            .with_audit_file(&audit_file)
+            .unwrap();
+        shared.reload(redis("6380")).unwrap();
+        shared.reload(redis("6380")).unwrap();
+        shared
+            .store_with(redis("6381").build(), Trigger::Signal)
+            .unwrap();
+        shared
+            .store_with(redis("6382").build(), Trigger::FileChange)
+            .unwrap();
+
+        let history = shared.history();
+        let summary: Vec<(u64, Trigger)> = history.iter().map(|e| (e.version, e.trigger)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (2, Trigger::Api),
+                (3, Trigger::Signal),
+                (4, Trigger::FileChange)
+            ]
+        );
+        assert_eq!(history[2].fingerprint, shared.load().fingerprint().unwrap());
+        assert_eq!(
+            history[2].diff.to_text(),
+            "redis:\n  ~ port: 6381 -> 6382\n"
+        );
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&audit_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["trigger"], "startup");
+        assert_eq!(lines[0]["diff"]["changes"][0]["kind"], "added");
+        assert_eq!(lines[3]["version"], 4);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_concurrent_readers() {
+        let shared = SharedConfig::from_builder(redis("6379")).unwrap();
+        let live = shared.live::<RedisConfig>().unwrap();
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let live = live.clone();
+                std::thread::spawn(move || {
+                    (0..1000).all(|_| matches!(live.get().port, 6379 | 6380))
+                })
+            })
+            .collect();
+        for port in ["6380", "6379", "6380"] {
+            shared.reload(redis(port)).unwrap();
+        }
+
+        assert!(readers.into_iter().all(|reader| reader.join().unwrap()));
+        assert_eq!(live.get().port, 6380);
+    }
+}
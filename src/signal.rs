@@ -0,0 +1,140 @@
+//! SIGHUP reload for Unix services (`signal` feature)
+//!
+//! Like nginx and postgres, a service can reload its configuration when ops
+//! tooling sends `SIGHUP`. [`reload_on_sighup`] starts a thread that, on
+//! every signal, re-runs the sources the current version was built from
+//! ([`ConfigManager::rebuild`](crate::ConfigManager::rebuild)), validates the
+//! result and publishes it through the [`SharedConfig`], change hooks
+//! included. Each attempt is logged through the `log` crate: success at
+//! `info`, failure with the reason at `error` (the current version stays).
+//!
+//! ```rust,no_run
+//! use tyl_config::{ConfigManager, RedisConfig};
+//!
+//! tyl_config::init(ConfigManager::builder().with_redis(RedisConfig::default())).unwrap();
+//! let _reloader = tyl_config::signal::reload_on_sighup(tyl_config::shared().unwrap()).unwrap();
+//! // ... serve requests; keep `_reloader` alive for as long as reloads should happen
+//! ```
+
+use crate::audit::Trigger;
+use crate::{ConfigResult, SharedConfig};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::{Handle, Signals};
+use std::thread::JoinHandle;
+use tyl_errors::TylError;
+
+/// Reloads on SIGHUP until stopped or dropped
+#[derive(Debug)]
+pub struct SighupReloader {
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Install a SIGHUP handler that reloads `shared`
+pub fn reload_on_sighup(shared: &SharedConfig) -> ConfigResult<SighupReloader> {
+    let mut signals = Signals::new([SIGHUP])
+        .map_err(|e| TylError::configuration(format!("Failed to install SIGHUP handler: {e}")))?;
+    let handle = signals.handle();
+    let shared = shared.clone();
+    let thread = std::thread::Builder::new()
+        .name("tyl-config-sighup".to_string())
+        .spawn(move || {
+            for _ in signals.forever() {
+                reload(&shared);
+            }
+        })
+        .map_err(|e| TylError::configuration(format!("Failed to start SIGHUP thread: {e}")))?;
+
+    Ok(SighupReloader {
+        handle,
+        thread: Some(thread),
+    })
+}
+
+impl SighupReloader {
+    /// Stop listening for SIGHUP
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SighupReloader {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn reload(shared: &SharedConfig) {
+    let result = shared
+        .rebuild_with(Trigger::Signal)
+        .and_then(|previous| previous.diff(&shared.load()));
+    match result {
+        Ok(diff) => log::info!(
+            "Configuration reloaded on SIGHUP: {} field(s) changed",
+            diff.changes.len()
+        ),
+        Err(e) => {
+            log::error!("Configuration reload on SIGHUP failed, keeping the current version: {e}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigManager, MapEnv, RedisConfig};
+    use std::time::{Duration, Instant};
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_sighup_reloads_from_sources() {
+        let dir = std::env::temp_dir().join(format!("tyl-config-sighup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        let redis = |port: u16| {
+            format!("redis:\n  host: cache\n  port: {port}\n  database: 0\n  pool_size: 5\n  timeout_seconds: 10\n")
+        };
+        std::fs::write(&path, redis(6379)).unwrap();
+
+        let shared = SharedConfig::from_builder(
+            ConfigManager::builder()
+                .with_environment(MapEnv::new())
+                .with_redis(RedisConfig::default())
+                .with_yaml_file(path.to_str().unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        let reloader = reload_on_sighup(&shared).unwrap();
+
+        std::fs::write(&path, redis(6380)).unwrap();
+        signal_hook::low_level::raise(SIGHUP).unwrap();
+        assert!(wait_for(|| shared.load().redis().unwrap().port == 6380));
+        assert_eq!(shared.history().last().unwrap().trigger, Trigger::Signal);
+
+        // An invalid version is logged and skipped
+        std::fs::write(&path, redis(6381).replace("pool_size: 5", "pool_size: 0")).unwrap();
+        signal_hook::low_level::raise(SIGHUP).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(shared.load().redis().unwrap().port, 6380);
+
+        reloader.stop();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
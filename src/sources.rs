@@ -0,0 +1,138 @@
+//! Recorded configuration sources
+//!
+//! The builder resolves every source while it builds, so a finished
+//! `ConfigManager` no longer knows where its values came from. To reload,
+//! the builder also records each call as a [`Source`]; replaying them on a
+//! fresh builder reads the env vars, files and directories again.
+
+use crate::lint::SourceFile;
+use crate::profile::Profile;
+use crate::registry::ErasedPlugin;
+use crate::{
+    fingerprint, validation, ConfigArgs, ConfigManagerBuilder, ConfigResult, Environment,
+    PostgresConfig, RedisConfig,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// One builder call, with plugins as given (before env vars were merged)
+#[derive(Clone)]
+pub(crate) enum Source {
+    Environment(Arc<dyn Environment>),
+    FingerprintKey(fingerprint::Key),
+    MigrationRewrite,
+    StrictDeprecations,
+    Validator(validation::Validator),
+    Profile(Profile),
+    Dotenv(PathBuf),
+    Postgres(PostgresConfig),
+    Redis(RedisConfig),
+    Plugin(Box<dyn ErasedPlugin>),
+    /// A required file fails the rebuild when it is gone
+    YamlFile {
+        path: String,
+        required: bool,
+    },
+    YamlFiles(Vec<PathBuf>),
+    Directory(PathBuf),
+    Args(ConfigArgs),
+}
+
+impl Source {
+    fn kind(&self) -> &'static str {
+        match self {
+            Source::Environment(_) => "environment",
+            Source::FingerprintKey(_) => "fingerprint_key",
+            Source::MigrationRewrite => "migration_rewrite",
+            Source::StrictDeprecations => "strict_deprecations",
+            Source::Validator(_) => "validator",
+            Source::Profile(_) => "profile",
+            Source::Dotenv(_) => "dotenv",
+            Source::Postgres(_) => "postgres",
+            Source::Redis(_) => "redis",
+            Source::Plugin(plugin) => plugin.section_name(),
+            Source::YamlFile { .. } | Source::YamlFiles(_) => "yaml",
+            Source::Directory(_) => "directory",
+            Source::Args(_) => "args",
+        }
+    }
+
+    fn replay(&self, builder: ConfigManagerBuilder) -> ConfigResult<ConfigManagerBuilder> {
+        Ok(match self {
+            Source::Environment(env) => builder.with_environment_arc(env.clone()),
+            Source::FingerprintKey(key) => builder.with_fingerprint_key_value(key.clone()),
+            Source::MigrationRewrite => builder.with_migration_rewrite(),
+            Source::StrictDeprecations => builder.with_strict_deprecations(),
+            Source::Validator(validator) => builder.with_validator_value(validator.clone()),
+            Source::Profile(profile) => builder.with_profile(*profile),
+            Source::Dotenv(path) => builder.with_dotenv(path)?,
+            Source::Postgres(config) => builder.with_postgres(config.clone()),
+            Source::Redis(config) => builder.with_redis(config.clone()),
+            Source::Plugin(plugin) => builder.with_erased_plugin(plugin.clone()),
+            Source::YamlFile { path, required } => {
+                if *required && !std::path::Path::new(path).exists() {
+                    return Err(tyl_errors::TylError::configuration(format!(
+                        "Config file not found: {path}"
+                    )));
+                }
+                builder.with_yaml_file(path)?
+            }
+            Source::YamlFiles(paths) => builder.with_yaml_files(paths)?,
+            Source::Directory(path) => builder.with_directory(path)?,
+            Source::Args(args) => builder.with_args(args)?,
+        })
+    }
+}
+
+/// Every source of a builder, in call order
+#[derive(Clone, Default)]
+pub(crate) struct Sources(Vec<Source>);
+
+impl Sources {
+    pub(crate) fn push(&mut self, source: Source) {
+        self.0.push(source);
+    }
+
+    /// The files and directories read so far that still exist
+    pub(crate) fn files(&self) -> Vec<SourceFile> {
+        let mut files = Vec::new();
+        for source in &self.0 {
+            match source {
+                Source::YamlFile { path, .. } => files.push(SourceFile::Yaml(path.into())),
+                Source::YamlFiles(paths) => {
+                    files.extend(paths.iter().cloned().map(SourceFile::Yaml));
+                }
+                Source::Args(args) => {
+                    files.extend(args.config_files.iter().cloned().map(SourceFile::Yaml));
+                }
+                Source::Dotenv(path) => files.push(SourceFile::Dotenv(path.clone())),
+                Source::Directory(path) => files.push(SourceFile::Directory(path.clone())),
+                _ => {}
+            }
+        }
+        files.retain(|file| match file {
+            SourceFile::Yaml(path) | SourceFile::Dotenv(path) | SourceFile::Directory(path) => {
+                path.exists()
+            }
+        });
+        files
+    }
+
+    /// A builder that went through the same calls again
+    pub(crate) fn replay(&self) -> ConfigResult<ConfigManagerBuilder> {
+        self.0
+            .iter()
+            .try_fold(ConfigManagerBuilder::new(), |builder, source| {
+                source.replay(builder)
+            })
+    }
+}
+
+// Only the kinds: environment snapshots and plugins hold secrets
+impl std::fmt::Debug for Sources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(Source::kind))
+            .finish()
+    }
+}
@@ -0,0 +1,500 @@
+//! Config template rendering and in-place updates
+//!
+//! [`update`] brings an existing, hand-edited YAML file up to date with the
+//! keys the plugins know about, without a YAML round trip that would drop
+//! comments and formatting. It works line by line on block-style YAML:
+//!
+//! - keys that are missing from a known section are inserted at the end of
+//!   that section, with their default value and doc comment;
+//! - missing sections are appended at the end of the file;
+//! - keys inside a known section that the plugin no longer has are kept but
+//!   flagged with a [`REMOVED_MARKER`] comment;
+//! - keys whose expected value is `null` (declared fields without a default,
+//!   such as unset `Option`s) are known but never inserted;
+//! - everything else (values, comments, blank lines, unknown top-level
+//!   sections) is left untouched.
+//!
+//! Files are replaced with [`write_atomic`], so a crash never leaves a
+//! half-written config behind, and keep their permissions.
+
+use crate::ConfigResult;
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+use tyl_errors::TylError;
+
+/// Start of the comment placed above keys no plugin knows about anymore
+pub const REMOVED_MARKER: &str = "# REMOVED:";
+
+/// Per-key doc comment lines (without the leading `#`), by dotted path
+pub type DocFn<'a> = &'a dyn Fn(&str) -> Vec<String>;
+
+/// What [`update`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateUpdate {
+    /// Dotted paths of inserted keys and sections
+    pub added: Vec<String>,
+    /// Dotted paths of keys flagged as no longer known
+    pub removed: Vec<String>,
+}
+
+impl TemplateUpdate {
+    /// Whether no key was added or flagged
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Render `key: value` at `indent`, with doc comments above every key
+pub fn render_entry(
+    key: &str,
+    value: &Value,
+    path: &str,
+    indent: usize,
+    doc: DocFn<'_>,
+    out: &mut Vec<String>,
+) -> ConfigResult<()> {
+    let pad = " ".repeat(indent);
+    for line in doc(path) {
+        out.push(format!("{pad}# {line}").trim_end().to_string());
+    }
+
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            out.push(format!("{pad}{}:", render_key(key)?));
+            for (child_key, child_value) in map {
+                let Some(child_key) = child_key.as_str() else {
+                    continue;
+                };
+                render_entry(
+                    child_key,
+                    child_value,
+                    &format!("{path}.{child_key}"),
+                    indent + 2,
+                    doc,
+                    out,
+                )?;
+            }
+        }
+        _ => {
+            let mut single = Mapping::new();
+            single.insert(Value::String(key.to_string()), value.clone());
+            let rendered = serde_yaml::to_string(&single)
+                .map_err(|e| TylError::serialization(format!("Failed to serialize YAML: {e}")))?;
+            out.extend(rendered.lines().map(|line| format!("{pad}{line}")));
+        }
+    }
+    Ok(())
+}
+
+fn render_key(key: &str) -> ConfigResult<String> {
+    let rendered = serde_yaml::to_string(key)
+        .map_err(|e| TylError::serialization(format!("Failed to serialize YAML: {e}")))?;
+    Ok(rendered.trim_end().to_string())
+}
+
+/// A mapping key found in the existing file
+struct KeyLine {
+    path: Vec<String>,
+    indent: usize,
+    line: usize,
+    /// Last line belonging to the key, its nested keys and values included
+    end: usize,
+}
+
+/// Update `existing` YAML text to contain every key of `expected`
+///
+/// `expected` maps section names to their default values, `null` for keys
+/// that exist but have no default to write. Returns the new text and what
+/// changed.
+pub fn update(
+    existing: &str,
+    expected: &Value,
+    doc: DocFn<'_>,
+) -> ConfigResult<(String, TemplateUpdate)> {
+    // Reject files that are not YAML up front, rather than editing them
+    serde_yaml::from_str::<Value>(existing)
+        .map_err(|e| TylError::configuration(format!("Failed to parse YAML: {e}")))?;
+
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+    let keys = scan_keys(&lines);
+    let mut report = TemplateUpdate::default();
+
+    // (line index to insert at, order at that index, lines), applied bottom-up.
+    // At the same index, keys added to a section go above a flag that
+    // belongs to the next key.
+    let mut insertions: Vec<(usize, u8, Vec<String>)> = Vec::new();
+
+    for key in &keys {
+        let Some(parent) = lookup(expected, &key.path[..key.path.len() - 1]) else {
+            continue;
+        };
+        // Only judge keys inside a known section (not top-level ones)
+        if key.path.len() < 2 || !parent.is_mapping() {
+            continue;
+        }
+        let name = &key.path[key.path.len() - 1];
+        if parent.get(name.as_str()).is_some() {
+            continue;
+        }
+
+        let path = key.path.join(".");
+        let already_flagged =
+            key.line > 0 && lines[key.line - 1].trim_start().starts_with(REMOVED_MARKER);
+        if !already_flagged {
+            let pad = " ".repeat(key.indent);
+            insertions.push((
+                key.line,
+                1,
+                vec![format!(
+                    "{pad}{REMOVED_MARKER} `{path}` is no longer a configuration key and is ignored"
+                )],
+            ));
+        }
+        report.removed.push(path);
+    }
+
+    for key in &keys {
+        let Some(Value::Mapping(children)) = lookup(expected, &key.path) else {
+            continue;
+        };
+        let child_indent = keys
+            .iter()
+            .find(|child| {
+                child.path.len() == key.path.len() + 1 && child.path.starts_with(&key.path)
+            })
+            .map(|child| child.indent)
+            .unwrap_or(key.indent + 2);
+
+        let mut added = Vec::new();
+        for (child_key, child_value) in children {
+            let Some(child_key) = child_key.as_str() else {
+                continue;
+            };
+            let present = keys.iter().any(|other| {
+                other.path.len() == key.path.len() + 1
+                    && other.path.starts_with(&key.path)
+                    && other.path[key.path.len()] == child_key
+            });
+            if present || child_value.is_null() {
+                continue;
+            }
+            let path = format!("{}.{child_key}", key.path.join("."));
+            let child_value = &without_nulls(child_value);
+            render_entry(child_key, child_value, &path, child_indent, doc, &mut added)?;
+            report.added.push(path);
+        }
+        if !added.is_empty() {
+            insertions.push((key.end + 1, 0, added));
+        }
+    }
+
+    // Sections missing entirely go to the end of the file
+    let mut appended = Vec::new();
+    if let Value::Mapping(sections) = expected {
+        for (section, value) in sections {
+            let Some(section) = section.as_str() else {
+                continue;
+            };
+            if keys.iter().any(|key| key.path == [section]) {
+                continue;
+            }
+            appended.push(String::new());
+            render_entry(
+                section,
+                &without_nulls(value),
+                section,
+                0,
+                doc,
+                &mut appended,
+            )?;
+            report.added.push(section.to_string());
+        }
+    }
+
+    insertions.sort_by_key(|(at, order, _)| (*at, *order));
+    for (at, _, new_lines) in insertions.into_iter().rev() {
+        lines.splice(at..at, new_lines);
+    }
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        lines.clear();
+        if appended.first().is_some_and(String::is_empty) {
+            appended.remove(0);
+        }
+    }
+    lines.extend(appended);
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Ok((text, report))
+}
+
+/// Find every block-style mapping key with its dotted path and extent
+fn scan_keys(lines: &[String]) -> Vec<KeyLine> {
+    let mut keys: Vec<KeyLine> = Vec::new();
+    // Indices into `keys` of the keys enclosing the current line
+    let mut open: Vec<usize> = Vec::new();
+    // Indentation of a block scalar (`|`, `>`) being skipped
+    let mut block_scalar: Option<usize> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+
+        if let Some(scalar_indent) = block_scalar {
+            if indent > scalar_indent {
+                extend_open(&mut keys, &open, index);
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        while let Some(&top) = open.last() {
+            if keys[top].indent >= indent {
+                open.pop();
+            } else {
+                break;
+            }
+        }
+
+        let Some((key, value)) = split_key(trimmed) else {
+            // Sequence items, flow continuations: part of the enclosing key
+            extend_open(&mut keys, &open, index);
+            continue;
+        };
+
+        let mut path: Vec<String> = open
+            .iter()
+            .map(|&i| keys[i].path.last().cloned().unwrap_or_default())
+            .collect();
+        path.push(key);
+        extend_open(&mut keys, &open, index);
+        keys.push(KeyLine {
+            path,
+            indent,
+            line: index,
+            end: index,
+        });
+        open.push(keys.len() - 1);
+
+        if value.starts_with('|') || value.starts_with('>') {
+            block_scalar = Some(indent);
+        }
+    }
+    keys
+}
+
+fn extend_open(keys: &mut [KeyLine], open: &[usize], index: usize) {
+    for &i in open {
+        keys[i].end = index;
+    }
+}
+
+/// Split `key: value` (or `key:`), ignoring `:` inside quotes
+fn split_key(trimmed: &str) -> Option<(String, &str)> {
+    if trimmed.starts_with('-') || trimmed.starts_with('[') || trimmed.starts_with('{') {
+        return None;
+    }
+
+    let mut quote = None;
+    for (i, c) in trimmed.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if i == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') => {
+                let rest = &trimmed[i + 1..];
+                if rest.is_empty() || rest.starts_with([' ', '\t']) {
+                    let key = trimmed[..i].trim();
+                    let key = key
+                        .strip_prefix(['"', '\''])
+                        .and_then(|k| k.strip_suffix(['"', '\'']))
+                        .unwrap_or(key);
+                    return Some((key.to_string(), rest.trim_start()));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `value` without the `null` entries of its mappings, at any depth
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Mapping(map) => Value::Mapping(
+            map.iter()
+                .filter(|(_, child)| !child.is_null())
+                .map(|(key, child)| (key.clone(), without_nulls(child)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn lookup<'a>(tree: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(tree, |node, key| node.get(key.as_str()))
+}
+
+/// Replace `path` with `content` via a temp file in the same directory and a rename
+///
+/// An existing file's permissions are copied to the temp file before any
+/// content is written, so a file holding secrets never becomes readable to
+/// others.
+pub fn write_atomic(path: impl AsRef<Path>, content: &str) -> ConfigResult<()> {
+    let path = path.as_ref();
+    let write_error = |e: std::io::Error| {
+        TylError::configuration(format!(
+            "Failed to write config file {}: {e}",
+            path.display()
+        ))
+    };
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| write_error(std::io::ErrorKind::InvalidInput.into()))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+
+    let result = (|| {
+        use std::io::Write;
+        let mut file = std::fs::File::create(&temp_path)?;
+        if let Ok(existing) = std::fs::metadata(path) {
+            file.set_permissions(existing.permissions())?;
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result.map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_doc(_: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn expected() -> Value {
+        serde_yaml::from_str(
+            "redis:\n  host: localhost\n  port: 6379\n  pool_size: 10\npostgres:\n  host: localhost\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_update_keeps_values_and_comments() {
+        let existing = "\
+# Team cache settings
+redis:
+  # points at the shared cluster
+  host: cache.internal   # do not change
+  port: 6380
+  legacy_mode: true
+
+extra:
+  untouched: yes
+";
+        let doc = |path: &str| vec![format!("doc for {path}")];
+        let (updated, report) = update(existing, &expected(), &doc).unwrap();
+
+        assert!(updated.starts_with("# Team cache settings\nredis:\n  # points at the shared cluster\n  host: cache.internal   # do not change\n  port: 6380\n"));
+        assert!(updated.contains(
+            "  # REMOVED: `redis.legacy_mode` is no longer a configuration key and is ignored\n  legacy_mode: true\n  # doc for redis.pool_size\n  pool_size: 10\n"
+        ));
+        assert!(updated.contains("extra:\n  untouched: yes\n"));
+        assert!(updated.ends_with(
+            "# doc for postgres\npostgres:\n  # doc for postgres.host\n  host: localhost\n"
+        ));
+        assert_eq!(report.added, vec!["redis.pool_size", "postgres"]);
+        assert_eq!(report.removed, vec!["redis.legacy_mode"]);
+
+        // The result is valid YAML with the user's values
+        let value: Value = serde_yaml::from_str(&updated).unwrap();
+        assert_eq!(value["redis"]["port"], Value::from(6380));
+        assert_eq!(value["redis"]["pool_size"], Value::from(10));
+    }
+
+    #[test]
+    fn test_update_is_idempotent() {
+        let existing = "redis:\n  host: cache\n  old: 1\n";
+        let (first, _) = update(existing, &expected(), &no_doc).unwrap();
+        let (second, report) = update(&first, &expected(), &no_doc).unwrap();
+
+        assert_eq!(first, second);
+        assert!(report.added.is_empty());
+        assert_eq!(report.removed, vec!["redis.old"]);
+    }
+
+    #[test]
+    fn test_block_scalars_are_not_keys() {
+        let existing = "redis:\n  host: |\n    port: 1\n    not: a key\n";
+        let (updated, report) = update(existing, &expected(), &no_doc).unwrap();
+
+        assert!(report.removed.is_empty());
+        assert_eq!(
+            report.added,
+            vec!["redis.port", "redis.pool_size", "postgres"]
+        );
+        let value: Value = serde_yaml::from_str(&updated).unwrap();
+        assert_eq!(value["redis"]["host"], Value::from("port: 1\nnot: a key\n"));
+        assert_eq!(value["redis"]["port"], Value::from(6379));
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let path =
+            std::env::temp_dir().join(format!("tyl-config-atomic-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, "new").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path =
+            std::env::temp_dir().join(format!("tyl-config-mode-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "password: old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomic(&path, "password: new").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_null_defaults_are_known_but_not_added() {
+        let expected: Value =
+            serde_yaml::from_str("redis:\n  host: localhost\n  url: null\n").unwrap();
+        let existing = "redis:\n  url: redis://cache:6379\n";
+        let (updated, report) = update(existing, &expected, &no_doc).unwrap();
+
+        assert_eq!(
+            updated,
+            "redis:\n  url: redis://cache:6379\n  host: localhost\n"
+        );
+        assert_eq!(report.added, vec!["redis.host"]);
+        assert!(report.removed.is_empty());
+
+        let (created, _) = update("", &expected, &no_doc).unwrap();
+        assert_eq!(created, "redis:\n  host: localhost\n");
+    }
+}
@@ -0,0 +1,364 @@
+//! Typed service configuration
+//!
+//! `ConfigManager::postgres()` returns an `Option` because any plugin may be
+//! left out at runtime. A service that cannot work without a plugin can
+//! declare it in the type instead: every `with_*` call on
+//! [`TypedConfigBuilder`] moves the plugin from [`Missing`] to present, and
+//! the resulting [`TypedConfig`] only has a (non-optional) accessor for the
+//! plugins that were provided.
+//!
+//! ```rust
+//! use tyl_config::{ConfigManager, Missing, PostgresConfig, TypedConfig};
+//!
+//! // The service declares what it needs...
+//! type OrderServiceConfig = TypedConfig<PostgresConfig, Missing>;
+//!
+//! fn load() -> OrderServiceConfig {
+//!     ConfigManager::typed()
+//!         .with_postgres(PostgresConfig::default())
+//!         .build()
+//! }
+//!
+//! // ...and gets a plain reference back, no unwrap needed
+//! let config = load();
+//! println!("{}", config.postgres().connection_url());
+//! ```
+//!
+//! Forgetting the plugin is a compile error:
+//!
+//! ```compile_fail
+//! use tyl_config::{ConfigManager, Missing, PostgresConfig, TypedConfig};
+//!
+//! let config: TypedConfig<PostgresConfig, Missing> = ConfigManager::typed().build();
+//! ```
+//!
+//! Custom plugins are tracked in a third parameter, a list of nested pairs
+//! ending in `()`: `with_plugin(Pool::default())` turns `C` into `(Pool, C)`,
+//! and [`TypedConfig::plugin`] is available for every type in the list.
+//!
+//! ```rust
+//! # use serde::{Deserialize, Serialize};
+//! # use tyl_config::{ConfigPlugin, ConfigResult, Environment};
+//! use tyl_config::{ConfigManager, Missing, MapEnv, TypedConfig};
+//!
+//! #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+//! struct Pool {
+//!     size: u32,
+//! }
+//! # impl ConfigPlugin for Pool {
+//! #     fn name(&self) -> &'static str { "pool" }
+//! #     fn env_prefix(&self) -> &'static str { "POOL" }
+//! #     fn validate(&self) -> ConfigResult<()> { Ok(()) }
+//! #     fn load_from_env(&self) -> ConfigResult<Self> { Ok(self.clone()) }
+//! #     fn merge_env_from(&mut self, _env: &dyn Environment) -> ConfigResult<()> { Ok(()) }
+//! # }
+//!
+//! let config: TypedConfig<Missing, Missing, (Pool, ())> = ConfigManager::typed()
+//!     .with_environment(MapEnv::new())
+//!     .with_plugin(Pool { size: 4 })
+//!     .build();
+//! assert_eq!(config.plugin::<Pool, _>().size, 4);
+//! ```
+
+use crate::{
+    ConfigArgs, ConfigErrors, ConfigManager, ConfigManagerBuilder, ConfigPlugin, ConfigResult,
+    Environment, PostgresConfig, RedisConfig,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+/// Marker for a plugin that was not provided
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Missing;
+
+/// Custom plugin list `C` contains `T`, at position `I`
+///
+/// Implemented for the nested pairs [`TypedConfigBuilder::with_plugin`]
+/// builds; `I` ([`Here`] / [`There`]) is inferred.
+pub trait Has<T, I> {}
+
+/// `T` is the head of the list
+#[derive(Debug)]
+pub struct Here;
+
+/// `T` is further down the list, at `I`
+#[derive(Debug)]
+pub struct There<I>(PhantomData<I>);
+
+impl<T, Rest> Has<T, Here> for (T, Rest) {}
+
+impl<T, U, Rest: Has<T, I>, I> Has<T, There<I>> for (U, Rest) {}
+
+/// Builder tracking the provided plugins in its type parameters
+///
+/// `P` is `PostgresConfig` once `with_postgres` was called, `Missing` before;
+/// `R` does the same for `RedisConfig`. `C` lists the custom plugins.
+pub struct TypedConfigBuilder<P = Missing, R = Missing, C = ()> {
+    inner: ConfigManagerBuilder,
+    _plugins: PhantomData<(P, R, C)>,
+}
+
+impl Default for TypedConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypedConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            inner: ConfigManagerBuilder::new(),
+            _plugins: PhantomData,
+        }
+    }
+}
+
+impl<P, R, C> TypedConfigBuilder<P, R, C> {
+    fn map<P2, R2, C2>(
+        self,
+        f: impl FnOnce(ConfigManagerBuilder) -> ConfigManagerBuilder,
+    ) -> TypedConfigBuilder<P2, R2, C2> {
+        TypedConfigBuilder {
+            inner: f(self.inner),
+            _plugins: PhantomData,
+        }
+    }
+
+    /// See [`ConfigManagerBuilder::with_environment`]
+    pub fn with_environment(self, env: impl Environment + 'static) -> Self {
+        self.map(|inner| inner.with_environment(env))
+    }
+
+    /// See [`ConfigManagerBuilder::with_dotenv`]
+    pub fn with_dotenv(self, path: impl AsRef<std::path::Path>) -> ConfigResult<Self> {
+        Ok(TypedConfigBuilder {
+            inner: self.inner.with_dotenv(path)?,
+            _plugins: PhantomData,
+        })
+    }
+
+    /// See [`ConfigManagerBuilder::with_yaml_file`]
+    ///
+    /// YAML sections only replace plugins, they never make a plugin required.
+    pub fn with_yaml_file(self, yaml_path: &str) -> ConfigResult<Self> {
+        Ok(TypedConfigBuilder {
+            inner: self.inner.with_yaml_file(yaml_path)?,
+            _plugins: PhantomData,
+        })
+    }
+
+    /// See [`ConfigManagerBuilder::with_directory`]
+    pub fn with_directory(self, path: impl AsRef<std::path::Path>) -> ConfigResult<Self> {
+        Ok(TypedConfigBuilder {
+            inner: self.inner.with_directory(path)?,
+            _plugins: PhantomData,
+        })
+    }
+
+    /// See [`ConfigManagerBuilder::with_args`]
+    pub fn with_args(self, args: &ConfigArgs) -> ConfigResult<Self> {
+        Ok(TypedConfigBuilder {
+            inner: self.inner.with_args(args)?,
+            _plugins: PhantomData,
+        })
+    }
+
+    /// Provide a custom plugin, making `TypedConfig::plugin::<T, _>()`
+    /// available (see [`ConfigManagerBuilder::with_plugin`])
+    pub fn with_plugin<T>(self, config: T) -> TypedConfigBuilder<P, R, (T, C)>
+    where
+        T: ConfigPlugin + Default + Serialize + DeserializeOwned + Clone + 'static,
+    {
+        self.map(|inner| inner.with_plugin(config))
+    }
+
+    /// Build, logging env and parse errors (see [`ConfigManagerBuilder::build`])
+    pub fn build(self) -> TypedConfig<P, R, C> {
+        TypedConfig {
+            manager: self.inner.build(),
+            _plugins: PhantomData,
+        }
+    }
+
+    /// Build, failing with every env and parse error collected so far
+    pub fn try_build(self) -> Result<TypedConfig<P, R, C>, ConfigErrors> {
+        Ok(TypedConfig {
+            manager: self.inner.try_build()?,
+            _plugins: PhantomData,
+        })
+    }
+}
+
+impl<R, C> TypedConfigBuilder<Missing, R, C> {
+    /// Provide the postgres plugin, making `TypedConfig::postgres()` available
+    pub fn with_postgres(self, config: PostgresConfig) -> TypedConfigBuilder<PostgresConfig, R, C> {
+        self.map(|inner| inner.with_postgres(config))
+    }
+}
+
+impl<P, C> TypedConfigBuilder<P, Missing, C> {
+    /// Provide the redis plugin, making `TypedConfig::redis()` available
+    pub fn with_redis(self, config: RedisConfig) -> TypedConfigBuilder<P, RedisConfig, C> {
+        self.map(|inner| inner.with_redis(config))
+    }
+}
+
+/// Configuration whose required plugins are guaranteed by its type
+#[derive(Debug, Clone)]
+pub struct TypedConfig<P = Missing, R = Missing, C = ()> {
+    manager: ConfigManager,
+    _plugins: PhantomData<(P, R, C)>,
+}
+
+impl<P, R, C> TypedConfig<P, R, C> {
+    /// The untyped manager, for APIs that work on any configuration
+    pub fn manager(&self) -> &ConfigManager {
+        &self.manager
+    }
+
+    pub fn into_manager(self) -> ConfigManager {
+        self.manager
+    }
+
+    /// Validate all configurations
+    pub fn validate(&self) -> ConfigResult<()> {
+        self.manager.validate()
+    }
+
+    /// A custom plugin provided with `with_plugin`; `I` is inferred
+    pub fn plugin<T: ConfigPlugin + 'static, I>(&self) -> &T
+    where
+        C: Has<T, I>,
+    {
+        self.manager
+            .plugin::<T>()
+            .expect("typestate guarantees the plugin is configured")
+    }
+}
+
+impl<R, C> TypedConfig<PostgresConfig, R, C> {
+    pub fn postgres(&self) -> &PostgresConfig {
+        self.manager
+            .postgres()
+            .expect("typestate guarantees postgres is configured")
+    }
+}
+
+impl<P, C> TypedConfig<P, RedisConfig, C> {
+    pub fn redis(&self) -> &RedisConfig {
+        self.manager
+            .redis()
+            .expect("typestate guarantees redis is configured")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MapEnv;
+
+    #[test]
+    fn test_typed_accessors_are_not_optional() {
+        let env = MapEnv::new().with("TYL_REDIS_HOST", "cache");
+
+        let config: TypedConfig<PostgresConfig, RedisConfig> = ConfigManager::typed()
+            .with_environment(env)
+            .with_postgres(PostgresConfig::default())
+            .with_redis(RedisConfig::default())
+            .build();
+
+        assert_eq!(config.postgres().port, 5432);
+        assert_eq!(config.redis().host, "cache");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_typed_yaml_keeps_required_plugin() {
+        let yaml_content = r#"
+redis:
+  host: yaml-redis
+  port: 6380
+  database: 2
+  pool_size: 5
+  timeout_seconds: 10
+"#;
+        let temp_path =
+            std::env::temp_dir().join(format!("tyl-config-typed-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, yaml_content).unwrap();
+
+        let config: TypedConfig<Missing, RedisConfig> = ConfigManager::typed()
+            .with_environment(MapEnv::new())
+            .with_redis(RedisConfig::default())
+            .with_yaml_file(temp_path.to_str().unwrap())
+            .unwrap()
+            .try_build()
+            .unwrap();
+
+        assert_eq!(config.redis().host, "yaml-redis");
+        assert!(config.manager().postgres().is_none());
+
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+    struct Pool {
+        size: u32,
+    }
+
+    #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+    struct Queue {
+        name: String,
+    }
+
+    macro_rules! plugin {
+        ($type:ty, $name:literal) => {
+            impl ConfigPlugin for $type {
+                fn name(&self) -> &'static str {
+                    $name
+                }
+                fn env_prefix(&self) -> &'static str {
+                    "TYL_TEST"
+                }
+                fn validate(&self) -> ConfigResult<()> {
+                    Ok(())
+                }
+                fn load_from_env(&self) -> ConfigResult<Self> {
+                    Ok(self.clone())
+                }
+                fn merge_env_from(&mut self, _env: &dyn Environment) -> ConfigResult<()> {
+                    Ok(())
+                }
+            }
+        };
+    }
+    plugin!(Pool, "pool");
+    plugin!(Queue, "queue");
+
+    #[test]
+    fn test_typed_custom_plugins() {
+        let config: TypedConfig<Missing, RedisConfig, (Queue, (Pool, ()))> = ConfigManager::typed()
+            .with_environment(MapEnv::new())
+            .with_plugin(Pool { size: 4 })
+            .with_redis(RedisConfig::default())
+            .with_plugin(Queue {
+                name: "orders".to_string(),
+            })
+            .build();
+
+        assert_eq!(config.plugin::<Pool, _>().size, 4);
+        assert_eq!(config.plugin::<Queue, _>().name, "orders");
+        assert_eq!(config.redis().port, 6379);
+    }
+
+    #[test]
+    fn test_typed_try_build_reports_errors() {
+        let env = MapEnv::new().with("TYL_POSTGRES_PORT", "abc");
+
+        let result = ConfigManager::typed()
+            .with_environment(env)
+            .with_postgres(PostgresConfig::default())
+            .try_build();
+
+        assert!(result.is_err());
+    }
+}
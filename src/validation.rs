@@ -0,0 +1,260 @@
+//! Validators over the whole configuration
+//!
+//! A plugin's `validate` only sees its own section. Constraints that span
+//! plugins are registered on the builder instead, and see the resolved
+//! `ConfigManager`:
+//!
+//! ```rust
+//! use tyl_config::{validation, ConfigManager, MapEnv, PostgresConfig, RedisConfig};
+//!
+//! let redis = RedisConfig {
+//!     url: Some("redis://localhost:5432/0".to_string()),
+//!     ..RedisConfig::default()
+//! };
+//! let config = ConfigManager::builder()
+//!     .with_environment(MapEnv::new())
+//!     .with_postgres(PostgresConfig::default())
+//!     .with_redis(redis)
+//!     .with_validator("pool_budget", validation::max_sum(["postgres"], "pool_size", 50))
+//!     .with_validator("endpoints", validation::distinct_endpoints())
+//!     .build();
+//!
+//! // Both point at localhost:5432
+//! let errors = config.validate_all().unwrap_err();
+//! assert_eq!(errors.iter().next().unwrap().0, "endpoints");
+//! ```
+//!
+//! `ConfigManager::validate` runs them after the plugin checks;
+//! `ConfigManager::validate_all` reports every plugin and validator failure
+//! together, each tagged with the section or validator name.
+
+use crate::{lint, ConfigManager, ConfigResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tyl_errors::TylError;
+
+type CheckFn = dyn Fn(&ConfigManager) -> ConfigResult<()> + Send + Sync;
+
+/// A named check on the whole configuration
+#[derive(Clone)]
+pub(crate) struct Validator {
+    name: String,
+    check: Arc<CheckFn>,
+}
+
+impl Validator {
+    pub(crate) fn new(
+        name: impl Into<String>,
+        check: impl Fn(&ConfigManager) -> ConfigResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            check: Arc::new(check),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn run(&self, config: &ConfigManager) -> ConfigResult<()> {
+        (self.check)(config)
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Validator").field(&self.name).finish()
+    }
+}
+
+/// The numeric `field` summed over the sections in `names` is at most `limit`
+///
+/// A total equal to the limit passes. Sections that are not configured or
+/// lack the field are not counted.
+pub fn max_sum<I, S>(
+    names: I,
+    field: &str,
+    limit: u64,
+) -> impl Fn(&ConfigManager) -> ConfigResult<()> + Send + Sync + 'static
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let included: Vec<String> = names.into_iter().map(Into::into).collect();
+    let field = field.to_string();
+    move |config| {
+        let mut total = 0;
+        let mut counted = Vec::new();
+        for (section, values) in sections(config)? {
+            if !included.iter().any(|name| name == section) {
+                continue;
+            }
+            if let Some(value) = values.get(field.as_str()).and_then(|v| v.as_u64()) {
+                total += value;
+                counted.push(format!("{section}.{field}"));
+            }
+        }
+        if total > limit {
+            return Err(TylError::validation(
+                &field,
+                format!(
+                    "total of {} is {total}, more than {limit}",
+                    counted.join(" + ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// No two sections point at the same `host:port`
+///
+/// The endpoint of a section connecting through a `url` is taken from the
+/// URL, with the scheme's default port when it has none. Sections without a
+/// host and port (or a URL with a known scheme) are not compared.
+pub fn distinct_endpoints() -> impl Fn(&ConfigManager) -> ConfigResult<()> + Send + Sync + 'static {
+    |config| {
+        let mut seen: HashMap<String, &'static str> = HashMap::new();
+        for (section, values) in sections(config)? {
+            let endpoint = match values.get("url").and_then(|url| url.as_str()) {
+                Some(url) if !url.is_empty() => url_endpoint(url),
+                _ => {
+                    let host = values.get("host").and_then(|v| v.as_str());
+                    let port = values.get("port").and_then(|v| v.as_u64());
+                    host.zip(port)
+                        .map(|(host, port)| format!("{}:{port}", host.to_ascii_lowercase()))
+                }
+            };
+            let Some(endpoint) = endpoint else {
+                continue;
+            };
+            if let Some(other) = seen.insert(endpoint.clone(), section) {
+                return Err(TylError::validation(
+                    format!("{section}.host"),
+                    format!("`{other}` and `{section}` both point at {endpoint}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `host:port` of a connection URL, lowercased
+fn url_endpoint(url: &str) -> Option<String> {
+    let host = lint::url_host(url)?.to_ascii_lowercase();
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // After the closing bracket of an IPv6 address
+    let after_host = host_port
+        .rsplit_once(']')
+        .map_or(host_port, |(_, rest)| rest);
+    let port = match after_host.rsplit_once(':') {
+        Some((_, port)) => port.parse().ok()?,
+        None => default_port(scheme)?,
+    };
+    Some(format!("{host}:{port}"))
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme.to_ascii_lowercase().as_str() {
+        "postgres" | "postgresql" => Some(5432),
+        "redis" | "rediss" => Some(6379),
+        _ => None,
+    }
+}
+
+fn sections(config: &ConfigManager) -> ConfigResult<Vec<(&'static str, serde_yaml::Value)>> {
+    config
+        .section_names()
+        .into_iter()
+        .map(|section| Ok((section, config.get_value(section)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapEnv, PostgresConfig, RedisConfig};
+
+    fn config(postgres: PostgresConfig, redis: RedisConfig) -> ConfigManager {
+        ConfigManager::builder()
+            .with_environment(MapEnv::new())
+            .with_postgres(postgres)
+            .with_redis(redis)
+            .build()
+    }
+
+    #[test]
+    fn test_max_sum() {
+        let config = config(PostgresConfig::default(), RedisConfig::default());
+        let postgres = config.postgres().unwrap().pool_size;
+        let both = postgres + config.redis().unwrap().pool_size;
+
+        // Only the listed sections count, and the limit itself passes
+        assert!(max_sum(["postgres"], "pool_size", postgres.into())(&config).is_ok());
+        assert!(max_sum(["postgres"], "pool_size", u64::from(postgres) - 1)(&config).is_err());
+        let error = max_sum(["postgres", "redis"], "pool_size", u64::from(both) - 1)(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("postgres.pool_size + redis.pool_size"),
+            "{error}"
+        );
+        assert!(max_sum(["postgres"], "missing", 0)(&config).is_ok());
+    }
+
+    #[test]
+    fn test_distinct_endpoints() {
+        let redis = RedisConfig {
+            port: 5432,
+            ..RedisConfig::default()
+        };
+        let clash = config(PostgresConfig::default(), redis);
+        let error = distinct_endpoints()(&clash).unwrap_err().to_string();
+        assert!(error.contains("localhost:5432"), "{error}");
+
+        // The endpoint of a URL is compared too, with the scheme's default port
+        let postgres = PostgresConfig {
+            url: Some("postgresql://u:p@DB:5432/app".to_string()),
+            ..PostgresConfig::default()
+        };
+        let redis = |url: &str| RedisConfig {
+            url: Some(url.to_string()),
+            ..RedisConfig::default()
+        };
+        let same = config(postgres.clone(), redis("redis://db:5432/0"));
+        assert!(distinct_endpoints()(&same).is_err());
+        assert!(distinct_endpoints()(&config(postgres.clone(), redis("redis://db/0"))).is_ok());
+        let default_port = RedisConfig {
+            host: "db".to_string(),
+            ..RedisConfig::default()
+        };
+        let postgres = PostgresConfig {
+            url: Some("postgres://[::1]/app".to_string()),
+            ..PostgresConfig::default()
+        };
+        assert!(distinct_endpoints()(&config(postgres, default_port)).is_ok());
+    }
+
+    #[test]
+    fn test_url_endpoint() {
+        assert_eq!(
+            url_endpoint("postgresql://u:p@Db:6543/app").as_deref(),
+            Some("db:6543")
+        );
+        assert_eq!(
+            url_endpoint("rediss://cache").as_deref(),
+            Some("cache:6379")
+        );
+        assert_eq!(
+            url_endpoint("postgres://[::1]/app").as_deref(),
+            Some("::1:5432")
+        );
+        assert_eq!(url_endpoint("http://example.com"), None);
+    }
+}
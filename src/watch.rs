@@ -0,0 +1,222 @@
+//! Reload when source files change
+//!
+//! [`reload_on_file_change`] starts a thread that checks, every `interval`,
+//! the files the current version was read from: YAML files and the files
+//! they include, `.env` files and key-per-file directories (a Kubernetes
+//! `..data` swap counts as a change). When a modification time or size
+//! differs from the last check, it re-runs the sources
+//! ([`ConfigManager::rebuild`](crate::ConfigManager::rebuild)) and publishes
+//! the result through the [`SharedConfig`] with [`Trigger::FileChange`].
+//! Like the SIGHUP reload, each attempt is logged: success at `info`,
+//! failure at `error` (the current version stays).
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tyl_config::{shared::SharedConfig, ConfigManager, RedisConfig};
+//!
+//! let shared = SharedConfig::from_builder(
+//!     ConfigManager::builder()
+//!         .with_redis(RedisConfig::default())
+//!         .with_yaml_file("config.yaml")
+//!         .unwrap(),
+//! )
+//! .unwrap();
+//! let _reloader = tyl_config::watch::reload_on_file_change(&shared, Duration::from_secs(2)).unwrap();
+//! // ... serve requests; keep `_reloader` alive for as long as reloads should happen
+//! ```
+
+use crate::audit::Trigger;
+use crate::lint::SourceFile;
+use crate::{yaml, ConfigManager, ConfigResult, SharedConfig};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use tyl_errors::TylError;
+
+/// Path, modification time and size of every watched file
+type Stamp = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+/// Reloads on file changes until stopped or dropped
+#[derive(Debug)]
+pub struct FileReloader {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Check the source files of `shared` every `interval` and reload when one
+/// changed
+pub fn reload_on_file_change(
+    shared: &SharedConfig,
+    interval: Duration,
+) -> ConfigResult<FileReloader> {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let shared = shared.clone();
+    let mut last = stamp(&shared.load());
+    let thread = std::thread::Builder::new()
+        .name("tyl-config-watch".to_string())
+        .spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let current = stamp(&shared.load());
+                if current != last {
+                    reload(&shared);
+                    last = stamp(&shared.load());
+                }
+            }
+        })
+        .map_err(|e| TylError::configuration(format!("Failed to start file watch thread: {e}")))?;
+
+    Ok(FileReloader {
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
+
+impl FileReloader {
+    /// Stop watching
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // Dropping the sender wakes the thread up
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for FileReloader {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn reload(shared: &SharedConfig) {
+    let result = shared
+        .rebuild_with(Trigger::FileChange)
+        .and_then(|previous| previous.diff(&shared.load()));
+    match result {
+        Ok(diff) => log::info!(
+            "Configuration reloaded after a file change: {} field(s) changed",
+            diff.changes.len()
+        ),
+        Err(e) => log::error!(
+            "Configuration reload after a file change failed, keeping the current version: {e}"
+        ),
+    }
+}
+
+fn stamp(config: &ConfigManager) -> Stamp {
+    let mut paths = Vec::new();
+    for file in config.source_files() {
+        match file {
+            SourceFile::Yaml(path) => match yaml::file_layers(&path) {
+                Ok(layers) => paths.extend(layers.into_iter().map(|(path, _)| path)),
+                Err(_) => paths.push(path),
+            },
+            SourceFile::Dotenv(path) => paths.push(path),
+            SourceFile::Directory(dir) => walk(&dir, &mut paths),
+        }
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let metadata = std::fs::symlink_metadata(&path).ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            let len = metadata.map_or(0, |m| m.len());
+            (path, modified, len)
+        })
+        .collect()
+}
+
+/// `dir` and everything below it, without following symlinked directories
+fn walk(dir: &Path, paths: &mut Vec<PathBuf>) {
+    paths.push(dir.to_path_buf());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+    for path in entries {
+        if std::fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()) {
+            walk(&path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigManager, MapEnv, RedisConfig};
+    use std::time::Instant;
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_file_change_reloads_from_sources() {
+        let dir = std::env::temp_dir().join(format!("tyl-config-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        let redis = |port: u16| {
+            format!("redis:\n  host: cache\n  port: {port}\n  database: 0\n  pool_size: 5\n  timeout_seconds: 10\n")
+        };
+        std::fs::write(&path, redis(6379)).unwrap();
+
+        let shared = SharedConfig::from_builder(
+            ConfigManager::builder()
+                .with_environment(MapEnv::new())
+                .with_redis(RedisConfig::default())
+                .with_yaml_file(path.to_str().unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        let reloader = reload_on_file_change(&shared, Duration::from_millis(20)).unwrap();
+
+        std::fs::write(&path, redis(16379)).unwrap();
+        assert!(wait_for(|| shared.load().redis().unwrap().port == 16379));
+        assert_eq!(
+            shared.history().last().unwrap().trigger,
+            Trigger::FileChange
+        );
+
+        // An invalid version is logged and skipped
+        std::fs::write(&path, redis(6381).replace("pool_size: 5", "pool_size: 0")).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(shared.load().redis().unwrap().port, 16379);
+
+        reloader.stop();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_stamp_follows_directory_entries() {
+        let dir = std::env::temp_dir().join(format!("tyl-config-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("redis.port"), "6379").unwrap();
+        let config = ConfigManager::builder()
+            .with_environment(MapEnv::new())
+            .with_redis(RedisConfig::default())
+            .with_directory(&dir)
+            .unwrap()
+            .build();
+
+        let before = stamp(&config);
+        std::fs::write(dir.join("redis.port"), "16379").unwrap();
+        assert_ne!(stamp(&config), before);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
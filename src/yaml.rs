@@ -0,0 +1,405 @@
+//! YAML document loading
+//!
+//! - [`deep_merge`] layers documents: mappings merge key by key, any other
+//!   value (scalars, sequences) is replaced by the overlay.
+//! - Include directives pull other files into a document. A top-level
+//!   `include:` key, or a `$include` key in any mapping, names one file or a
+//!   list of files, relative to the including file. Included content is
+//!   merged first and the keys next to the directive override it:
+//!
+//! ```yaml
+//! include: tyl-base.yaml       # platform defaults
+//! postgres:
+//!   $include: secrets/pg.yaml  # merged into this mapping only
+//!   pool_size: 20              # wins over both included files
+//! ```
+//!
+//! Include cycles are reported as errors; including the same file from two
+//! places is fine.
+
+use crate::ConfigResult;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+use tyl_errors::TylError;
+
+/// Top-level include directive
+pub const INCLUDE_KEY: &str = "include";
+
+/// Include directive accepted in any mapping
+pub const NESTED_INCLUDE_KEY: &str = "$include";
+
+/// Deep-merge `overlay` into `base`
+pub fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Deep-merge string leaves of `overlay` into `base`, typed like the values
+/// they replace
+///
+/// Sources without types (directory keys, command-line values) produce
+/// strings; `"5433"` over a port becomes a number and `"true"` over a flag a
+/// boolean. A string that does not fit is kept as-is, so deserializing the
+/// plugin reports the mismatch against the right field.
+pub fn merge_coerced(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_coerced(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, Value::String(raw)) => *base = coerce_scalar(raw, base),
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Interpret `raw` with the type of `shape`, falling back to a string
+pub fn coerce_scalar(raw: String, shape: &Value) -> Value {
+    let typed = match shape {
+        Value::Number(_) => serde_yaml::from_str::<Value>(&raw)
+            .ok()
+            .filter(Value::is_number),
+        Value::Bool(_) => serde_yaml::from_str::<Value>(&raw)
+            .ok()
+            .filter(Value::is_bool),
+        _ => None,
+    };
+    typed.unwrap_or(Value::String(raw))
+}
+
+/// Read a YAML file and resolve its include directives
+pub fn load_file(path: impl AsRef<Path>) -> ConfigResult<Value> {
+    let mut stack = Vec::new();
+    load_file_inner(path.as_ref(), &mut stack)
+}
+
+/// Read several YAML files and deep-merge them in order (later files win)
+///
+/// Missing files are skipped, like in `ConfigManagerBuilder::with_yaml_file`.
+pub fn load_files<I, P>(paths: I) -> ConfigResult<Value>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut merged = Value::Null;
+    for path in paths {
+        let path = path.as_ref();
+        if path.exists() {
+            deep_merge(&mut merged, load_file(path)?);
+        }
+    }
+    Ok(merged)
+}
+
+/// Resolve the include directives of `document` as if it were the content
+/// of the file at `path`
+pub(crate) fn resolve_includes_at(document: Value, path: &Path) -> ConfigResult<Value> {
+    let canonical = path.canonicalize().map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    let base_dir = canonical
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    resolve_includes(document, &base_dir, &mut vec![canonical], true)
+}
+
+/// The file at `path` and every file it includes, each with only the keys
+/// it sets itself, placed where they end up in the resolved document
+///
+/// The file comes first, then its includes depth-first.
+pub(crate) fn file_layers(path: &Path) -> ConfigResult<Vec<(PathBuf, Value)>> {
+    let mut layers = Vec::new();
+    collect_layers(path, &[], &mut Vec::new(), &mut layers)?;
+    Ok(layers)
+}
+
+fn collect_layers(
+    path: &Path,
+    prefix: &[Value],
+    stack: &mut Vec<PathBuf>,
+    layers: &mut Vec<(PathBuf, Value)>,
+) -> ConfigResult<()> {
+    let canonical = path.canonicalize().map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    // Cycles are reported by `load_file`
+    if stack.contains(&canonical) {
+        return Ok(());
+    }
+    let yaml_content = std::fs::read_to_string(&canonical).map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    let value: Value = serde_yaml::from_str(&yaml_content).map_err(|e| {
+        TylError::configuration(format!("Failed to parse YAML {}: {e}", path.display()))
+    })?;
+    let base_dir = canonical
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let index = layers.len();
+    layers.push((path.to_path_buf(), Value::Null));
+    stack.push(canonical);
+    let own = own_keys(value, prefix, &base_dir, stack, layers, true);
+    stack.pop();
+
+    // Nest the file's keys under the mapping that includes it
+    layers[index].1 = prefix.iter().rev().fold(own?, |value, key| {
+        let mut map = Mapping::new();
+        map.insert(key.clone(), value);
+        Value::Mapping(map)
+    });
+    Ok(())
+}
+
+/// `value` without its include directives, collecting the included files
+fn own_keys(
+    value: Value,
+    prefix: &[Value],
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    layers: &mut Vec<(PathBuf, Value)>,
+    top_level: bool,
+) -> ConfigResult<Value> {
+    let Value::Mapping(map) = value else {
+        return Ok(value);
+    };
+    let mut own = Mapping::new();
+    for (key, value) in map {
+        let is_directive = key
+            .as_str()
+            .is_some_and(|key| key == NESTED_INCLUDE_KEY || (top_level && key == INCLUDE_KEY));
+        if is_directive {
+            for include in include_paths(&value)? {
+                collect_layers(&base_dir.join(include), prefix, stack, layers)?;
+            }
+        } else {
+            let nested: Vec<Value> = prefix.iter().cloned().chain([key.clone()]).collect();
+            own.insert(
+                key,
+                own_keys(value, &nested, base_dir, stack, layers, false)?,
+            );
+        }
+    }
+    Ok(Value::Mapping(own))
+}
+
+fn load_file_inner(path: &Path, stack: &mut Vec<PathBuf>) -> ConfigResult<Value> {
+    let canonical = path.canonicalize().map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(TylError::configuration(format!(
+            "Include cycle detected: {}",
+            chain.join(" -> ")
+        )));
+    }
+
+    let yaml_content = std::fs::read_to_string(&canonical).map_err(|e| {
+        TylError::configuration(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    let value: Value = serde_yaml::from_str(&yaml_content).map_err(|e| {
+        TylError::configuration(format!("Failed to parse YAML {}: {e}", path.display()))
+    })?;
+
+    let base_dir = canonical
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    stack.push(canonical);
+    let resolved = resolve_includes(value, &base_dir, stack, true);
+    stack.pop();
+    resolved
+}
+
+fn resolve_includes(
+    value: Value,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    top_level: bool,
+) -> ConfigResult<Value> {
+    let Value::Mapping(map) = value else {
+        return Ok(value);
+    };
+
+    let mut included = Value::Null;
+    let mut rest = Mapping::new();
+    for (key, value) in map {
+        let is_directive = key
+            .as_str()
+            .is_some_and(|key| key == NESTED_INCLUDE_KEY || (top_level && key == INCLUDE_KEY));
+        if is_directive {
+            for include in include_paths(&value)? {
+                let include_path = base_dir.join(include);
+                deep_merge(&mut included, load_file_inner(&include_path, stack)?);
+            }
+        } else {
+            rest.insert(key, resolve_includes(value, base_dir, stack, false)?);
+        }
+    }
+
+    // Keys written next to the directive override the included content
+    deep_merge(&mut included, Value::Mapping(rest));
+    Ok(included)
+}
+
+fn include_paths(directive: &Value) -> ConfigResult<Vec<&str>> {
+    let invalid =
+        || TylError::configuration("Include directive must be a file path or a list of file paths");
+
+    match directive {
+        Value::String(path) => Ok(vec![path.as_str()]),
+        Value::Sequence(paths) => paths
+            .iter()
+            .map(|path| path.as_str().ok_or_else(invalid))
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tyl-config-{name}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_deep_merge_mappings() {
+        let mut base: Value =
+            serde_yaml::from_str("postgres:\n  host: base\n  port: 5432\ntags: [a, b]\n").unwrap();
+        let overlay: Value =
+            serde_yaml::from_str("postgres:\n  host: override\ntags: [c]\n").unwrap();
+
+        deep_merge(&mut base, overlay);
+
+        assert_eq!(base["postgres"]["host"], Value::from("override"));
+        assert_eq!(base["postgres"]["port"], Value::from(5432));
+        // Sequences are replaced, not concatenated
+        assert_eq!(base["tags"], serde_yaml::from_str::<Value>("[c]").unwrap());
+    }
+
+    #[test]
+    fn test_merge_coerced_follows_base_types() {
+        let mut base: Value =
+            serde_yaml::from_str("port: 5432\nssl: false\nhost: localhost\n").unwrap();
+        let overlay: Value =
+            serde_yaml::from_str("port: '5433'\nssl: 'true'\nhost: '12345'\nextra: '1'\n").unwrap();
+
+        merge_coerced(&mut base, overlay);
+
+        assert_eq!(base["port"], Value::from(5433));
+        assert_eq!(base["ssl"], Value::from(true));
+        // String fields stay strings even when they look like numbers
+        assert_eq!(base["host"], Value::from("12345"));
+        assert_eq!(base["extra"], Value::from("1"));
+
+        // Values that do not fit are left for deserialization to reject
+        merge_coerced(&mut base, serde_yaml::from_str("port: abc\n").unwrap());
+        assert_eq!(base["port"], Value::from("abc"));
+    }
+
+    #[test]
+    fn test_includes_relative_to_including_file() {
+        let dir = temp_dir("includes");
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::write(
+            dir.join("shared/base.yaml"),
+            "redis:\n  host: base-redis\n  port: 6379\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("shared/pg.yaml"), "host: pg-host\nport: 5432\n").unwrap();
+        std::fs::write(
+            dir.join("service.yaml"),
+            "include: shared/base.yaml\nredis:\n  port: 6380\npostgres:\n  $include: shared/pg.yaml\n  port: 5433\n",
+        )
+        .unwrap();
+
+        let value = load_file(dir.join("service.yaml")).unwrap();
+
+        assert_eq!(value["redis"]["host"], Value::from("base-redis"));
+        assert_eq!(value["redis"]["port"], Value::from(6380));
+        assert_eq!(value["postgres"]["host"], Value::from("pg-host"));
+        assert_eq!(value["postgres"]["port"], Value::from(5433));
+        assert!(value.get(INCLUDE_KEY).is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_include_cycle_detected() {
+        let dir = temp_dir("cycle");
+        std::fs::write(dir.join("a.yaml"), "include: b.yaml\n").unwrap();
+        std::fs::write(dir.join("b.yaml"), "include: [a.yaml]\n").unwrap();
+
+        let error = load_file(dir.join("a.yaml")).unwrap_err();
+        assert!(error.to_string().contains("Include cycle detected"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_load_files_merges_in_order() {
+        let dir = temp_dir("layers");
+        std::fs::write(
+            dir.join("base.yaml"),
+            "redis:\n  host: base\n  port: 6379\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("override.yaml"), "redis:\n  host: override\n").unwrap();
+
+        let value = load_files([
+            dir.join("base.yaml"),
+            dir.join("missing.yaml"),
+            dir.join("override.yaml"),
+        ])
+        .unwrap();
+
+        assert_eq!(value["redis"]["host"], Value::from("override"));
+        assert_eq!(value["redis"]["port"], Value::from(6379));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}